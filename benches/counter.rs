@@ -0,0 +1,209 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use raii_counter_futures::{Counter, CounterPool, WeakCounter};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+
+fn clone_drop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("clone_drop");
+    let counter = Counter::new();
+    group.bench_function("counter", |b| b.iter(|| drop(black_box(counter.clone()))));
+
+    // The hand-rolled equivalent, for comparison.
+    let count = Arc::new(AtomicUsize::new(1));
+    group.bench_function("arc_atomic", |b| {
+        b.iter(|| {
+            let count = black_box(&count);
+            count.fetch_add(1, Ordering::AcqRel);
+            count.fetch_sub(1, Ordering::AcqRel);
+        })
+    });
+    group.finish();
+}
+
+/// Runs `iters` iterations of `f` on each of `THREADS` threads at once,
+/// passing each its index, and returns the wall time for all of them to
+/// finish.
+fn contended(iters: u64, f: impl Fn(usize) + Send + Sync + 'static) -> Duration {
+    let f = Arc::new(f);
+    let barrier = Arc::new(Barrier::new(THREADS + 1));
+    let handles: Vec<_> = (0..THREADS)
+        .map(|thread| {
+            let f = Arc::clone(&f);
+            let barrier = Arc::clone(&barrier);
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..iters {
+                    f(thread);
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let start = Instant::now();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+    start.elapsed()
+}
+
+fn contended_clone_drop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended_clone_drop");
+    group.bench_function("counter", |b| {
+        b.iter_custom(|iters| {
+            let counter = Counter::new();
+            contended(iters, move |_| drop(black_box(counter.clone())))
+        })
+    });
+    group.bench_function("arc_atomic", |b| {
+        b.iter_custom(|iters| {
+            let count = Arc::new(AtomicUsize::new(1));
+            contended(iters, move |_| {
+                count.fetch_add(1, Ordering::AcqRel);
+                count.fetch_sub(1, Ordering::AcqRel);
+            })
+        })
+    });
+    group.finish();
+}
+
+/// Each thread clones and drops its own Counter, all of them created back to
+/// back so their counts end up close together in memory. Compare runs with
+/// and without the `padded` feature to see what false sharing costs.
+fn adjacent_clone_drop(c: &mut Criterion) {
+    let mut group = c.benchmark_group("adjacent_clone_drop");
+    group.bench_function("counter", |b| {
+        b.iter_custom(|iters| {
+            let counters: Vec<_> = (0..THREADS).map(|_| Counter::new()).collect();
+            contended(iters, move |thread| {
+                drop(black_box(counters[thread].clone()))
+            })
+        })
+    });
+    group.finish();
+}
+
+fn contended_bounded_upgrade(c: &mut Criterion) {
+    let mut group = c.benchmark_group("contended_bounded_upgrade");
+    group.bench_function("nearly_full", |b| {
+        b.iter_custom(|iters| {
+            // One slot short of full, so most attempts race for the last one.
+            let weak = WeakCounter::new_with_capacity(THREADS);
+            let held = weak.spawn_upgrade_many(THREADS - 1);
+            let elapsed = contended(iters, move |_| drop(black_box(weak.try_spawn_upgrade())));
+            drop(held);
+            elapsed
+        })
+    });
+    group.finish();
+}
+
+/// Pooled acquires against plain upgrades, alone and on `THREADS` threads.
+fn pool(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pool");
+    let weak = WeakCounter::new();
+    group.bench_function("spawn_upgrade", |b| {
+        b.iter(|| drop(black_box(weak.spawn_upgrade())))
+    });
+    let counter_pool = CounterPool::new(WeakCounter::new());
+    group.bench_function("acquire", |b| {
+        b.iter(|| drop(black_box(counter_pool.acquire())))
+    });
+    group.bench_function("contended_spawn_upgrade", |b| {
+        b.iter_custom(|iters| {
+            let weak = WeakCounter::new();
+            contended(iters, move |_| drop(black_box(weak.spawn_upgrade())))
+        })
+    });
+    group.bench_function("contended_acquire", |b| {
+        b.iter_custom(|iters| {
+            let pool = CounterPool::new(WeakCounter::new());
+            contended(iters, move |_| drop(black_box(pool.acquire())))
+        })
+    });
+    group.finish();
+}
+
+/// Waiters on a count bouncing off zero: each bounce wakes every
+/// `wait_for_empty` waiter to re-check the count, but only one
+/// `wait_for_empty_fair` waiter. Times the bounces and the final drain until
+/// every waiter has resolved.
+fn thundering_herd(c: &mut Criterion) {
+    const WAITERS: usize = 1000;
+    const BOUNCES: usize = 100;
+
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(THREADS)
+        .enable_all()
+        .build()
+        .unwrap();
+    let mut group = c.benchmark_group("thundering_herd");
+    group.sample_size(10);
+    for (name, fair) in [("wait_for_empty", false), ("wait_for_empty_fair", true)] {
+        group.bench_function(name, |b| {
+            b.iter_custom(|iters| {
+                runtime.block_on(async {
+                    let mut elapsed = Duration::ZERO;
+                    for _ in 0..iters {
+                        let weak = WeakCounter::new();
+                        let mut counter = weak.spawn_upgrade();
+                        let waiters: Vec<_> = (0..WAITERS)
+                            .map(|_| {
+                                let weak = weak.clone();
+                                tokio::spawn(async move {
+                                    if fair {
+                                        weak.wait_for_empty_fair().await
+                                    } else {
+                                        weak.wait_for_empty().await
+                                    }
+                                })
+                            })
+                            .collect();
+                        // Let every waiter register before timing.
+                        tokio::time::sleep(Duration::from_millis(10)).await;
+
+                        let start = Instant::now();
+                        for _ in 0..BOUNCES {
+                            drop(counter);
+                            counter = weak.spawn_upgrade();
+                        }
+                        drop(counter);
+                        for waiter in waiters {
+                            waiter.await.unwrap();
+                        }
+                        elapsed += start.elapsed();
+                    }
+                    elapsed
+                })
+            })
+        });
+    }
+    group.finish();
+}
+
+fn count(c: &mut Criterion) {
+    let mut group = c.benchmark_group("count");
+    let counter = Counter::new();
+    group.bench_function("count", |b| b.iter(|| black_box(&counter).count()));
+    group.bench_function("count_relaxed", |b| {
+        b.iter(|| black_box(&counter).count_relaxed())
+    });
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    clone_drop,
+    contended_clone_drop,
+    adjacent_clone_drop,
+    contended_bounded_upgrade,
+    pool,
+    thundering_herd,
+    count
+);
+criterion_main!(benches);
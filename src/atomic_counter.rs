@@ -0,0 +1,68 @@
+use crate::internal;
+
+/// A count changed through explicit `increment` and `decrement` calls, like
+/// a plain atomic, rather than through RAII guards, while still supporting
+/// `wait_for_empty`.
+///
+/// The caller is responsible for balancing every increment with a decrement.
+/// Decrementing a count of zero panics in debug builds and wraps in release
+/// builds, like dropping more Counters than were created would. Clones share
+/// the same count.
+///
+/// ```rust
+/// use raii_counter_futures::AtomicCounter;
+///
+/// # futures::executor::block_on(async {
+/// let counter = AtomicCounter::new();
+/// counter.increment();
+/// assert_eq!(counter.get(), 1);
+/// counter.decrement();
+/// # #[cfg(feature = "std")]
+/// counter.wait_for_empty().await;
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct AtomicCounter {
+    counter: internal::Counter,
+}
+
+impl AtomicCounter {
+    /// Create an AtomicCounter with a count of zero.
+    pub fn new() -> AtomicCounter {
+        AtomicCounter {
+            counter: internal::Counter::new(0),
+        }
+    }
+
+    /// Add one to the count.
+    #[inline]
+    pub fn increment(&self) {
+        self.counter.fetch_add(1);
+    }
+
+    /// Subtract one from the count, waking waiters if that drains it.
+    #[inline]
+    pub fn decrement(&self) {
+        self.counter.fetch_sub(1);
+    }
+
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn get(&self) -> usize {
+        self.counter.get()
+    }
+
+    /// Returns a future that waits until the count is zero. Resolves
+    /// immediately if it already is.
+    #[cfg(feature = "std")]
+    pub async fn wait_for_empty(&self) {
+        self.counter.wait_for_empty().await
+    }
+}
+
+impl Default for AtomicCounter {
+    fn default() -> Self {
+        AtomicCounter::new()
+    }
+}
@@ -0,0 +1,39 @@
+use crate::sync::spin_loop;
+#[cfg(feature = "std")]
+use crate::sync::yield_now;
+
+/// Exponential backoff for compare-and-swap loops that keep losing the race
+/// to other threads. Spins for twice as long after each failure, then starts
+/// yielding the thread instead once spinning long hasn't helped. Without
+/// `std` it keeps spinning.
+pub(crate) struct Backoff {
+    step: u32,
+}
+
+impl Backoff {
+    /// Past this many failures, spinning gives way to yielding.
+    const SPIN_LIMIT: u32 = 6;
+
+    #[inline]
+    pub(crate) fn new() -> Backoff {
+        Backoff { step: 0 }
+    }
+
+    /// Wait a little longer than the previous call before retrying.
+    #[inline]
+    pub(crate) fn snooze(&mut self) {
+        if self.step <= Self::SPIN_LIMIT {
+            for _ in 0..1 << self.step {
+                spin_loop();
+            }
+            self.step += 1;
+        } else {
+            #[cfg(feature = "std")]
+            yield_now();
+            #[cfg(not(feature = "std"))]
+            for _ in 0..1 << Self::SPIN_LIMIT {
+                spin_loop();
+            }
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use alloc::vec::Vec;
+use core::iter::FromIterator;
+
+use crate::{CountInt, GenericCounter};
+
+/// Holds the Counters of a batch of work together, e.g. the guards of a
+/// batch of spawned tasks, releasing them all when the batch is dropped.
+///
+/// The Counters may belong to different counts.
+///
+/// ```rust
+/// use raii_counter_futures::{CounterBatch, WeakCounter};
+///
+/// let weak = WeakCounter::new();
+/// let batch: CounterBatch = (0..3).map(|_| weak.spawn_upgrade()).collect();
+/// assert_eq!(batch.contributed(), 3);
+/// batch.drain_all();
+/// assert!(weak.is_empty());
+/// ```
+#[derive(Debug, Clone)]
+#[must_use = "dropping the batch immediately releases its Counters; bind it to a variable"]
+pub struct CounterBatch<T: CountInt = usize> {
+    counters: Vec<GenericCounter<T>>,
+}
+
+impl<T: CountInt> CounterBatch<T> {
+    pub fn new() -> CounterBatch<T> {
+        CounterBatch {
+            counters: Vec::new(),
+        }
+    }
+
+    /// Add a Counter to the batch. It is released along with the rest.
+    pub fn push(&mut self, counter: GenericCounter<T>) {
+        self.counters.push(counter);
+    }
+
+    /// The number of Counters in the batch
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.counters.len()
+    }
+
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.counters.is_empty()
+    }
+
+    /// The sum of the sizes of the Counters in the batch, i.e. how much the
+    /// batch adds to the counts it holds.
+    ///
+    /// Panics in debug builds if the sum overflows `T`.
+    pub fn contributed(&self) -> T {
+        self.counters
+            .iter()
+            .fold(T::ZERO, |sum, counter| sum + counter.size())
+    }
+
+    /// Release every Counter in the batch at once. Equivalent to dropping it.
+    pub fn drain_all(self) {}
+}
+
+impl<T: CountInt> Default for CounterBatch<T> {
+    fn default() -> Self {
+        CounterBatch::new()
+    }
+}
+
+impl<T: CountInt> Extend<GenericCounter<T>> for CounterBatch<T> {
+    fn extend<I: IntoIterator<Item = GenericCounter<T>>>(&mut self, iter: I) {
+        self.counters.extend(iter);
+    }
+}
+
+impl<T: CountInt> FromIterator<GenericCounter<T>> for CounterBatch<T> {
+    fn from_iter<I: IntoIterator<Item = GenericCounter<T>>>(iter: I) -> Self {
+        CounterBatch {
+            counters: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl<T: CountInt> From<Vec<GenericCounter<T>>> for CounterBatch<T> {
+    fn from(counters: Vec<GenericCounter<T>>) -> Self {
+        CounterBatch { counters }
+    }
+}
@@ -0,0 +1,81 @@
+use crate::internal;
+
+/// A budget that starts at `n` and is drawn down as work is admitted, the
+/// inverse of a Counter: each `BudgetGuard` takes one unit out of the budget
+/// while alive and puts it back when dropped. Useful for quotas and rate
+/// limits where the interesting question is how much is left.
+///
+/// Clones share the same budget.
+///
+/// ```rust
+/// use raii_counter_futures::Budget;
+///
+/// # futures::executor::block_on(async {
+/// let budget = Budget::new(1);
+/// let guard = budget.try_acquire().unwrap();
+/// assert!(budget.try_acquire().is_none());
+/// drop(guard);
+/// # #[cfg(feature = "std")]
+/// budget.wait_for_available().await;
+/// assert_eq!(budget.remaining(), 1);
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Budget {
+    counter: internal::Counter,
+}
+
+/// Holds one unit of a `Budget` until dropped.
+#[derive(Debug)]
+#[must_use = "dropping the guard immediately returns it to the budget; bind it to a variable"]
+pub struct BudgetGuard {
+    counter: internal::Counter,
+}
+
+impl Budget {
+    pub fn new(n: usize) -> Budget {
+        Budget {
+            counter: internal::Counter::new(n),
+        }
+    }
+
+    /// Take one unit out of the budget, or return `None` if it is exhausted.
+    pub fn try_acquire(&self) -> Option<BudgetGuard> {
+        self.counter.try_fetch_sub(1).ok()?;
+        Some(BudgetGuard {
+            counter: self.counter.clone(),
+        })
+    }
+
+    /// Wait until the budget isn't exhausted, then take one unit out of it.
+    /// Dropping the future before it resolves leaves the budget untouched.
+    #[cfg(feature = "std")]
+    pub async fn acquire(&self) -> BudgetGuard {
+        loop {
+            if let Some(guard) = self.try_acquire() {
+                return guard;
+            }
+            self.wait_for_available().await;
+        }
+    }
+
+    /// The number of units left. This method is inherently racey, see
+    /// `WeakCounter::count`.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.counter.get()
+    }
+
+    /// Wait until at least one unit is left. Resolves immediately if the
+    /// budget isn't exhausted.
+    #[cfg(feature = "std")]
+    pub async fn wait_for_available(&self) {
+        self.counter.wait_for_at_least(1).await
+    }
+}
+
+impl Drop for BudgetGuard {
+    fn drop(&mut self) {
+        self.counter.fetch_add(1);
+    }
+}
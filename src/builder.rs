@@ -0,0 +1,73 @@
+use crate::internal::{self, OnEmpty, Options};
+use crate::{Counter, WeakCounter};
+
+/// Configures a count before creating the first handle to it, for when more
+/// than one of the `new_*` constructors' settings are needed at once.
+///
+/// ```rust
+/// use raii_counter_futures::CounterBuilder;
+///
+/// let counter = CounterBuilder::new().name("db_pool").size(3).build();
+/// assert_eq!(counter.to_string(), "Counter(name=db_pool, count=3, size=3)");
+/// ```
+#[derive(Debug)]
+pub struct CounterBuilder {
+    size: usize,
+    options: Options,
+}
+
+impl CounterBuilder {
+    pub fn new() -> CounterBuilder {
+        CounterBuilder {
+            size: 1,
+            options: Options::default(),
+        }
+    }
+
+    /// The size of the Counter returned by `build`, 1 by default. Ignored by
+    /// `build_weak`.
+    pub fn size(mut self, size: usize) -> CounterBuilder {
+        self.size = size;
+        self
+    }
+
+    /// A name shown in `Display` output and in `tracing` events.
+    pub fn name(mut self, name: &'static str) -> CounterBuilder {
+        self.options.name = Some(name);
+        self
+    }
+
+    /// Stops upgrades from taking the count above `capacity`, like
+    /// `WeakCounter::new_with_capacity`. The Counter returned by `build` is
+    /// not limited.
+    pub fn capacity(mut self, capacity: usize) -> CounterBuilder {
+        self.options.capacity = Some(capacity);
+        self
+    }
+
+    /// Invokes `on_empty` every time the count transitions to zero, like
+    /// `Counter::new_with_on_empty`.
+    pub fn on_empty(mut self, on_empty: impl Fn() + Send + Sync + 'static) -> CounterBuilder {
+        self.options.on_empty = Some(OnEmpty::new(on_empty));
+        self
+    }
+
+    /// Create a Counter of the configured size.
+    pub fn build(self) -> Counter {
+        Counter {
+            counter: internal::Counter::with_options(self.size, self.options),
+            size: self.size,
+        }
+    }
+
+    /// Create a WeakCounter with a count of zero.
+    pub fn build_weak(self) -> WeakCounter {
+        WeakCounter::from_counter(internal::Counter::with_options(0, self.options))
+    }
+}
+
+impl Default for CounterBuilder {
+    fn default() -> Self {
+        CounterBuilder::new()
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Once};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A runtime-agnostic future that resolves once a duration has elapsed.
+///
+/// Every `Delay` is driven by one shared timer thread, spawned the first time
+/// a `Delay` is created, which sleeps until the earliest pending deadline.
+/// Dropping a `Delay` before it fires takes it off the queue.
+pub(crate) struct Delay {
+    /// Where the delay sits in the queue, `None` if it can never fire.
+    key: Option<Key>,
+    state: Arc<Mutex<State>>,
+}
+
+/// The deadline first, so the queue is ordered by it, then an id to tell
+/// apart delays that are due at the same instant.
+type Key = (Instant, usize);
+
+#[derive(Default)]
+struct State {
+    elapsed: bool,
+    waker: Option<Waker>,
+}
+
+struct Timers {
+    queue: Mutex<BTreeMap<Key, Arc<Mutex<State>>>>,
+    /// Notified when a new delay becomes the earliest one.
+    changed: Condvar,
+}
+
+static TIMERS: Timers = Timers {
+    queue: Mutex::new(BTreeMap::new()),
+    changed: Condvar::new(),
+};
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+static START: Once = Once::new();
+
+impl Delay {
+    pub(crate) fn new(dur: Duration) -> Delay {
+        let state = Arc::new(Mutex::new(State::default()));
+
+        // A deadline too far out to represent is never reached.
+        let key = Instant::now()
+            .checked_add(dur)
+            .map(|deadline| (deadline, NEXT_ID.fetch_add(1, Ordering::Relaxed)));
+        if let Some(key) = key {
+            START.call_once(|| {
+                thread::spawn(run);
+            });
+
+            let mut queue = TIMERS.queue.lock().unwrap();
+            let earliest = queue
+                .first_key_value()
+                .is_none_or(|(first, _)| key < *first);
+            queue.insert(key, Arc::clone(&state));
+            if earliest {
+                TIMERS.changed.notify_one();
+            }
+        }
+
+        Delay { key, state }
+    }
+}
+
+/// The timer thread: fires every delay whose deadline has passed, then
+/// sleeps until the next one is due or an earlier one is queued.
+fn run() {
+    let mut queue = TIMERS.queue.lock().unwrap();
+    loop {
+        let now = Instant::now();
+        match queue.first_key_value().map(|(&(deadline, _), _)| deadline) {
+            None => queue = TIMERS.changed.wait(queue).unwrap(),
+            Some(deadline) if deadline > now => {
+                queue = TIMERS
+                    .changed
+                    .wait_timeout(queue, deadline - now)
+                    .unwrap()
+                    .0;
+            }
+            Some(_) => {
+                let (_, state) = queue.pop_first().unwrap();
+                // Wake outside the queue's lock, the waker may run arbitrary code.
+                drop(queue);
+                let waker = {
+                    let mut state = state.lock().unwrap();
+                    state.elapsed = true;
+                    state.waker.take()
+                };
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+                queue = TIMERS.queue.lock().unwrap();
+            }
+        }
+    }
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.state.lock().unwrap();
+        if state.elapsed {
+            Poll::Ready(())
+        } else {
+            state.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if let Some(key) = self.key {
+            TIMERS.queue.lock().unwrap().remove(&key);
+        }
+    }
+}
@@ -0,0 +1,9 @@
+/// The output of whichever of two futures resolved first, see
+/// [`WeakCounter::wait_for_empty_or`].
+///
+/// [`WeakCounter::wait_for_empty_or`]: crate::WeakCounter::wait_for_empty_or
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Either<A, B> {
+    Left(A),
+    Right(B),
+}
@@ -0,0 +1,40 @@
+use core::fmt::{self, Display, Formatter};
+
+/// Error returned when incrementing a counter would overflow the integer type
+/// its count is kept in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CounterOverflow;
+
+impl Display for CounterOverflow {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "counter overflowed its integer type")
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CounterOverflow {}
+
+/// Error returned by `count_checked` when more has been subtracted from a
+/// count over its lifetime than was ever added to it, which can only happen
+/// once it has been desynced from its handles.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Desync {
+    /// The lifetime total added to the count, including its initial value.
+    pub added: u64,
+    /// The lifetime total subtracted, including what saturating subtractions
+    /// clamped away.
+    pub subtracted: u64,
+}
+
+impl Display for Desync {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "counter desynced: subtracted {} but only {} was added",
+            self.subtracted, self.added
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Desync {}
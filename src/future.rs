@@ -0,0 +1,53 @@
+use crate::int::CountInt;
+use crate::internal;
+use event_listener::EventListener;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Future that resolves once the count is zero, see
+/// [`GenericWeakCounter::empty_future`].
+///
+/// Unlike the future returned by `wait_for_empty`, this has a name, so it can
+/// be stored in a struct field without boxing. It holds its own reference to
+/// the count rather than borrowing the handle it came from.
+///
+/// [`GenericWeakCounter::empty_future`]: crate::GenericWeakCounter::empty_future
+#[derive(Debug)]
+#[must_use = "futures do nothing unless polled"]
+pub struct EmptyFuture<T: CountInt = usize> {
+    counter: internal::Counter<T>,
+    listener: Option<EventListener>,
+}
+
+impl<T: CountInt> EmptyFuture<T> {
+    pub(crate) fn new(counter: internal::Counter<T>) -> EmptyFuture<T> {
+        EmptyFuture {
+            counter,
+            listener: None,
+        }
+    }
+}
+
+impl<T: CountInt> Future for EmptyFuture<T> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        loop {
+            if self.counter.get() == T::ZERO {
+                self.listener = None;
+                return Poll::Ready(());
+            }
+
+            match &mut self.listener {
+                // Register and loop around to re-check the count, in case it
+                // drained before the listener existed.
+                None => self.listener = Some(self.counter.listen_empty()),
+                Some(listener) => match Pin::new(listener).poll(cx) {
+                    Poll::Ready(()) => self.listener = None,
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use core::fmt::{Debug, Display};
+
+#[cfg(target_has_atomic = "32")]
+use crate::sync::AtomicU32;
+#[cfg(target_has_atomic = "64")]
+use crate::sync::AtomicU64;
+use crate::sync::{AtomicUsize, Ordering};
+
+/// An unsigned integer type a count can be kept in, along with the atomic it
+/// is stored as.
+///
+/// Implemented for `usize`, and for `u32` and `u64` on targets with atomics of
+/// that width. Closeable counters reserve the most significant bit, halving
+/// the largest count they can hold. This trait is sealed and can't be
+/// implemented outside of this crate.
+pub trait CountInt: sealed::Sealed + Debug + Display + Send + Sync + Unpin + 'static {}
+
+pub(crate) mod sealed {
+    use core::fmt::Debug;
+    use core::ops::{Add, BitAnd, BitOr, Not, Sub};
+
+    use crate::sync::Ordering;
+
+    /// The integer and atomic operations the counter is built from.
+    pub trait Sealed:
+        Copy
+        + Ord
+        + Add<Output = Self>
+        + Sub<Output = Self>
+        + BitAnd<Output = Self>
+        + BitOr<Output = Self>
+        + Not<Output = Self>
+    {
+        type Atomic: Debug + Send + Sync;
+
+        const ZERO: Self;
+        const ONE: Self;
+        const MAX: Self;
+        /// Only the most significant bit set.
+        const HIGH_BIT: Self;
+
+        fn new_atomic(value: Self) -> Self::Atomic;
+        fn load(atomic: &Self::Atomic, order: Ordering) -> Self;
+        fn store(atomic: &Self::Atomic, value: Self, order: Ordering);
+        fn swap(atomic: &Self::Atomic, value: Self, order: Ordering) -> Self;
+        #[cfg(not(loom))]
+        fn get_mut(atomic: &mut Self::Atomic) -> &mut Self;
+        fn fetch_add(atomic: &Self::Atomic, value: Self, order: Ordering) -> Self;
+        fn fetch_sub(atomic: &Self::Atomic, value: Self, order: Ordering) -> Self;
+        fn fetch_max(atomic: &Self::Atomic, value: Self, order: Ordering) -> Self;
+        fn compare_exchange_weak(
+            atomic: &Self::Atomic,
+            current: Self,
+            new: Self,
+            success: Ordering,
+            failure: Ordering,
+        ) -> Result<Self, Self>;
+        fn fetch_update(
+            atomic: &Self::Atomic,
+            set_order: Ordering,
+            fetch_order: Ordering,
+            f: impl FnMut(Self) -> Option<Self>,
+        ) -> Result<Self, Self>;
+
+        fn checked_add(self, rhs: Self) -> Option<Self>;
+        fn checked_sub(self, rhs: Self) -> Option<Self>;
+        fn saturating_sub(self, rhs: Self) -> Self;
+        fn wrapping_sub(self, rhs: Self) -> Self;
+        /// Multiplies by `n`, returning `None` if the result doesn't fit.
+        fn checked_mul_usize(self, n: usize) -> Option<Self>;
+        /// Divides by `n`, returning the quotient and remainder.
+        fn div_rem_usize(self, n: usize) -> (Self, Self);
+        /// Widens to a `u64`, which holds every supported count.
+        fn to_u64(self) -> u64;
+    }
+}
+
+macro_rules! impl_count_int {
+    ($int:ty, $atomic:ty) => {
+        impl CountInt for $int {}
+
+        impl sealed::Sealed for $int {
+            type Atomic = $atomic;
+
+            const ZERO: $int = 0;
+            const ONE: $int = 1;
+            const MAX: $int = <$int>::MAX;
+            const HIGH_BIT: $int = 1 << (<$int>::BITS - 1);
+
+            #[inline]
+            fn new_atomic(value: $int) -> $atomic {
+                <$atomic>::new(value)
+            }
+
+            #[inline]
+            fn load(atomic: &$atomic, order: Ordering) -> $int {
+                atomic.load(order)
+            }
+
+            #[inline]
+            fn store(atomic: &$atomic, value: $int, order: Ordering) {
+                atomic.store(value, order)
+            }
+
+            #[inline]
+            fn swap(atomic: &$atomic, value: $int, order: Ordering) -> $int {
+                atomic.swap(value, order)
+            }
+
+            #[cfg(not(loom))]
+            #[inline]
+            fn get_mut(atomic: &mut $atomic) -> &mut $int {
+                atomic.get_mut()
+            }
+
+            #[inline]
+            fn fetch_add(atomic: &$atomic, value: $int, order: Ordering) -> $int {
+                atomic.fetch_add(value, order)
+            }
+
+            #[inline]
+            fn fetch_sub(atomic: &$atomic, value: $int, order: Ordering) -> $int {
+                atomic.fetch_sub(value, order)
+            }
+
+            #[inline]
+            fn fetch_max(atomic: &$atomic, value: $int, order: Ordering) -> $int {
+                atomic.fetch_max(value, order)
+            }
+
+            #[inline]
+            fn compare_exchange_weak(
+                atomic: &$atomic,
+                current: $int,
+                new: $int,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$int, $int> {
+                atomic.compare_exchange_weak(current, new, success, failure)
+            }
+
+            #[inline]
+            fn fetch_update(
+                atomic: &$atomic,
+                set_order: Ordering,
+                fetch_order: Ordering,
+                f: impl FnMut($int) -> Option<$int>,
+            ) -> Result<$int, $int> {
+                atomic.fetch_update(set_order, fetch_order, f)
+            }
+
+            #[inline]
+            fn checked_add(self, rhs: $int) -> Option<$int> {
+                <$int>::checked_add(self, rhs)
+            }
+
+            #[inline]
+            fn checked_sub(self, rhs: $int) -> Option<$int> {
+                <$int>::checked_sub(self, rhs)
+            }
+
+            #[inline]
+            fn saturating_sub(self, rhs: $int) -> $int {
+                <$int>::saturating_sub(self, rhs)
+            }
+
+            #[inline]
+            fn wrapping_sub(self, rhs: $int) -> $int {
+                <$int>::wrapping_sub(self, rhs)
+            }
+
+            #[inline]
+            fn checked_mul_usize(self, n: usize) -> Option<$int> {
+                use core::convert::TryFrom;
+
+                <$int>::try_from(n).ok().and_then(|n| self.checked_mul(n))
+            }
+
+            #[inline]
+            fn div_rem_usize(self, n: usize) -> ($int, $int) {
+                use core::convert::TryFrom;
+
+                match <$int>::try_from(n) {
+                    Ok(n) => (self / n, self % n),
+                    // Larger than any value of self.
+                    Err(_) => (0, self),
+                }
+            }
+
+            #[inline]
+            fn to_u64(self) -> u64 {
+                self as u64
+            }
+        }
+    };
+}
+
+impl_count_int!(usize, AtomicUsize);
+#[cfg(target_has_atomic = "32")]
+impl_count_int!(u32, AtomicU32);
+#[cfg(target_has_atomic = "64")]
+impl_count_int!(u64, AtomicU64);
@@ -1,51 +1,1149 @@
-use futures_intrusive::sync::ManualResetEvent;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::Arc;
+use alloc::boxed::Box;
+#[cfg(feature = "history")]
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Deref;
 
-#[derive(Debug, Clone)]
-pub(crate) struct Counter {
-    counter: Arc<AtomicUsize>,
-    event: Arc<ManualResetEvent>,
+use crate::backoff::Backoff;
+use crate::int::CountInt;
+#[cfg(all(feature = "tracing", debug_assertions))]
+use crate::sync::AtomicBool;
+#[cfg(feature = "diagnostics")]
+use crate::sync::AtomicU64;
+use crate::sync::{Arc, AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use crate::sync::{Condvar, Mutex};
+
+#[cfg(feature = "std")]
+use crate::timer;
+#[cfg(feature = "std")]
+use core::future::Future;
+#[cfg(feature = "std")]
+use core::pin::Pin;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use event_listener::{Event, EventListener};
+
+/// Callback invoked each time the count transitions to zero.
+pub(crate) struct OnEmpty(Box<dyn Fn() + Send + Sync>);
+
+impl OnEmpty {
+    pub(crate) fn new(f: impl Fn() + Send + Sync + 'static) -> OnEmpty {
+        OnEmpty(Box::new(f))
+    }
+
+    /// Wraps `f` to only be invoked by the first drain.
+    #[cfg(feature = "std")]
+    pub(crate) fn once(f: impl FnOnce() + Send + 'static) -> OnEmpty {
+        let f: Box<dyn FnOnce() + Send> = Box::new(f);
+        let f = std::sync::Mutex::new(Some(f));
+        OnEmpty::new(move || {
+            let f = f.lock().unwrap_or_else(|e| e.into_inner()).take();
+            if let Some(f) = f {
+                f();
+            }
+        })
+    }
+}
+
+impl fmt::Debug for OnEmpty {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("OnEmpty")
+    }
+}
+
+#[cfg(feature = "std")]
+pub(crate) type AsyncCleanup = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = ()> + Send>> + Send>;
+
+/// Async teardown awaited by the `release_async` that drains the count, at
+/// most once.
+#[cfg(feature = "std")]
+pub(crate) struct AsyncLastDrop(std::sync::Mutex<Option<AsyncCleanup>>);
+
+#[cfg(feature = "std")]
+impl AsyncLastDrop {
+    pub(crate) fn new<F: Future<Output = ()> + Send + 'static>(
+        f: impl FnOnce() -> F + Send + 'static,
+    ) -> AsyncLastDrop {
+        let f: AsyncCleanup = Box::new(move || Box::pin(f()));
+        AsyncLastDrop(std::sync::Mutex::new(Some(f)))
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for AsyncLastDrop {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("AsyncLastDrop")
+    }
+}
+
+/// Aligns the wrapped value to its own cache line when the `padded` feature is
+/// enabled, so that counts updated from different cores don't false-share.
+#[cfg_attr(feature = "padded", repr(align(64)))]
+#[derive(Debug)]
+struct CachePadded<T>(T);
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Where the count itself lives.
+#[derive(Debug)]
+enum Count<T: CountInt> {
+    Owned(CachePadded<T::Atomic>),
+    Static(&'static T::Atomic),
+    Shared(alloc::sync::Arc<T::Atomic>),
+}
+
+impl<T: CountInt> Deref for Count<T> {
+    type Target = T::Atomic;
+
+    #[inline]
+    fn deref(&self) -> &T::Atomic {
+        match self {
+            Count::Owned(count) => count,
+            Count::Static(count) => count,
+            Count::Shared(count) => count,
+        }
+    }
+}
+
+/// Wakes up everything waiting on the count, only available with `std`.
+///
+/// Nothing here is ever reset by an increment. Waiters register a listener,
+/// re-check the count and only then park, which means a drain racing with the
+/// registration either shows up in the re-check or notifies the listener.
+///
+/// `empty` is only notified when a decrement leaves the count at zero, and
+/// each listener is woken at most once, so a waiter can't spin: every trip
+/// around its loop is paid for by a distinct drain, even if the count has
+/// been raised again by the time the waiter re-checks it.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Events {
+    empty: Event,
+    /// Waiters woken one at a time, each waking the next once it resolves.
+    empty_fair: Event,
+    changed: Event,
+    /// The number of times the count has transitioned to zero.
+    drains: AtomicUsize,
+    blocking: (Mutex<()>, Condvar),
+}
+
+#[cfg(feature = "std")]
+impl Events {
+    fn new() -> Events {
+        Events {
+            empty: Event::new(),
+            empty_fair: Event::new(),
+            changed: Event::new(),
+            drains: AtomicUsize::new(0),
+            blocking: (Mutex::new(()), Condvar::new()),
+        }
+    }
+
+    #[inline]
+    fn notify<T: CountInt>(&self, count: T) {
+        if count == T::ZERO {
+            self.empty.notify(usize::MAX);
+            self.empty_fair.notify(1);
+
+            // Taking the lock ensures a blocking waiter is either parked on the
+            // condvar or has yet to check the count.
+            let (lock, condvar) = &self.blocking;
+            #[cfg(any(loom, not(feature = "parking_lot")))]
+            drop(lock.lock().unwrap());
+            #[cfg(all(not(loom), feature = "parking_lot"))]
+            drop(lock.lock());
+            condvar.notify_all();
+        }
+        self.changed.notify(usize::MAX);
+    }
+}
+
+/// Lifetime totals of everything added to and subtracted from the count, only
+/// tracked with the `diagnostics` feature. The initial count counts as added.
+#[cfg(feature = "diagnostics")]
+#[derive(Debug)]
+struct Totals {
+    added: AtomicU64,
+    subbed: AtomicU64,
+    /// What saturating subtractions were asked to subtract beyond the count.
+    clamped: AtomicU64,
+}
+
+#[cfg(feature = "history")]
+const HISTORY_LEN: usize = 32;
+
+/// The most recent values of the count, only kept with the `history`
+/// feature. Each change claims the next slot and overwrites it, so readers
+/// may race with writers; it is a debugging aid, not a log.
+#[cfg(feature = "history")]
+struct History<T: CountInt> {
+    slots: [T::Atomic; HISTORY_LEN],
+    /// The total number of samples ever recorded.
+    next: AtomicUsize,
+}
+
+#[cfg(feature = "history")]
+impl<T: CountInt> History<T> {
+    fn new(count: T) -> History<T> {
+        let history = History {
+            slots: core::array::from_fn(|_| T::new_atomic(T::ZERO)),
+            next: AtomicUsize::new(0),
+        };
+        history.record(count);
+        history
+    }
+
+    #[inline]
+    fn record(&self, count: T) {
+        let slot = self.next.fetch_add(1, Ordering::Relaxed) % HISTORY_LEN;
+        T::store(&self.slots[slot], count, Ordering::Relaxed);
+    }
+
+    fn recent(&self) -> Vec<T> {
+        let next = self.next.load(Ordering::Relaxed);
+        (next.saturating_sub(HISTORY_LEN)..next)
+            .map(|i| T::load(&self.slots[i % HISTORY_LEN], Ordering::Relaxed))
+            .collect()
+    }
+}
+
+#[cfg(feature = "history")]
+impl<T: CountInt> fmt::Debug for History<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.recent()).finish()
+    }
+}
+
+/// The shared state behind every handle to a count, kept in a single
+/// allocation so cloning a handle only touches one reference count.
+#[derive(Debug)]
+struct Inner<T: CountInt> {
+    counter: Count<T>,
+    #[cfg(feature = "std")]
+    events: Events,
+    weak: AtomicUsize,
+    peak: T::Atomic,
+    #[cfg(feature = "diagnostics")]
+    totals: Totals,
+    #[cfg(feature = "history")]
+    history: History<T>,
+    saturating: bool,
+    closeable: bool,
+    monotonic: bool,
+    capacity: Option<T>,
+    name: Option<&'static str>,
+    on_empty: Option<OnEmpty>,
+    #[cfg(feature = "std")]
+    async_last_drop: Option<AsyncLastDrop>,
+    /// Whether the count has ever drained, for warning about revivals.
+    #[cfg(all(feature = "tracing", debug_assertions))]
+    has_drained: AtomicBool,
+}
+
+impl<T: CountInt> Inner<T> {
+    /// Set in the count of a closeable counter once it has drained.
+    ///
+    /// Keeping the flag in the same atomic as the count lets upgrades check it
+    /// and increment in a single compare-and-swap, so an upgrade can never
+    /// revive a counter that is draining concurrently.
+    const CLOSED: T = T::HIGH_BIT;
+
+    fn new(count: T) -> Inner<T> {
+        Inner {
+            counter: Count::Owned(CachePadded(T::new_atomic(count))),
+            #[cfg(feature = "std")]
+            events: Events::new(),
+            weak: AtomicUsize::new(0),
+            peak: T::new_atomic(count),
+            #[cfg(feature = "diagnostics")]
+            totals: Totals {
+                added: AtomicU64::new(count.to_u64()),
+                subbed: AtomicU64::new(0),
+                clamped: AtomicU64::new(0),
+            },
+            #[cfg(feature = "history")]
+            history: History::new(count),
+            saturating: false,
+            closeable: false,
+            monotonic: false,
+            capacity: None,
+            name: None,
+            on_empty: None,
+            #[cfg(feature = "std")]
+            async_last_drop: None,
+            #[cfg(all(feature = "tracing", debug_assertions))]
+            has_drained: AtomicBool::new(false),
+        }
+    }
+
+    /// Strips the closed flag from a raw value of `counter`.
+    #[inline]
+    fn value(&self, raw: T) -> T {
+        if self.closeable {
+            raw & !Self::CLOSED
+        } else {
+            raw
+        }
+    }
+
+    /// Whether a raw value of `counter` has the closed flag set.
+    #[inline]
+    fn closed(&self, raw: T) -> bool {
+        self.closeable && raw & Self::CLOSED != T::ZERO
+    }
+
+    /// The largest count this counter can hold.
+    #[inline]
+    fn max(&self) -> T {
+        if self.closeable {
+            Self::CLOSED - T::ONE
+        } else {
+            T::MAX
+        }
+    }
+
+    /// The largest count upgrades may take this counter to.
+    #[inline]
+    fn limit(&self) -> T {
+        self.capacity
+            .map_or(self.max(), |capacity| capacity.min(self.max()))
+    }
+}
+
+/// The optional settings a counter can be built with, gathered so they can be
+/// applied together.
+#[derive(Debug)]
+pub(crate) struct Options<T = usize> {
+    pub(crate) name: Option<&'static str>,
+    pub(crate) capacity: Option<T>,
+    pub(crate) on_empty: Option<OnEmpty>,
+}
+
+impl<T> Default for Options<T> {
+    fn default() -> Self {
+        Options {
+            name: None,
+            capacity: None,
+            on_empty: None,
+        }
+    }
 }
 
-impl Counter {
-    pub(crate) fn new(count: usize) -> Self {
+#[derive(Debug)]
+pub(crate) struct Counter<T: CountInt = usize> {
+    inner: Arc<Inner<T>>,
+}
+
+impl<T: CountInt> Clone for Counter<T> {
+    fn clone(&self) -> Self {
         Self {
-            counter: Arc::new(AtomicUsize::new(count)),
-            event: Arc::new(ManualResetEvent::new(false)),
+            inner: Arc::clone(&self.inner),
         }
     }
+}
 
-    pub(crate) async fn wait_for_empty(&self) {
-        while self.get() != 0 {
-            self.event.wait().await;
+impl<T: CountInt> Counter<T> {
+    pub(crate) fn new(count: T) -> Self {
+        Self {
+            inner: Arc::new(Inner::new(count)),
+        }
+    }
+
+    /// Creates a counter that invokes `on_empty` every time the count
+    /// transitions to zero. The callback runs on the thread that performed
+    /// the final decrement, after the new count is visible.
+    pub(crate) fn new_with_on_empty(count: T, on_empty: OnEmpty) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                on_empty: Some(on_empty),
+                ..Inner::new(count)
+            }),
         }
     }
 
+    /// Creates a counter whose `async_last_drop` is awaited by the
+    /// `release_async` that first drains it.
+    #[cfg(feature = "std")]
+    pub(crate) fn new_with_async_last_drop(count: T, last_drop: AsyncLastDrop) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                async_last_drop: Some(last_drop),
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Takes the async teardown, if there is one that hasn't been taken yet.
+    #[cfg(feature = "std")]
+    pub(crate) fn take_async_last_drop(&self) -> Option<AsyncCleanup> {
+        let last_drop = self.inner.async_last_drop.as_ref()?;
+        last_drop.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+    }
+
+    /// Creates a counter with a name attached, used to tell counters apart in
+    /// diagnostics.
+    pub(crate) fn new_named(count: T, name: &'static str) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name: Some(name),
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Creates a counter with every setting in `options` applied.
+    pub(crate) fn with_options(count: T, options: Options<T>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                name: options.name,
+                capacity: options.capacity,
+                on_empty: options.on_empty,
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Creates a counter backed by a static atomic rather than one allocated
+    /// alongside the rest of the shared state.
+    pub(crate) fn from_static(count: &'static T::Atomic) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                counter: Count::Static(count),
+                ..Inner::new(T::load(count, Ordering::Acquire))
+            }),
+        }
+    }
+
+    /// Creates a counter backed by an atomic shared with other code, adding
+    /// `amount` to it for the first handle.
+    pub(crate) fn from_arc(count: alloc::sync::Arc<T::Atomic>, amount: T) -> Self {
+        let previous = T::fetch_add(&count, amount, Ordering::AcqRel);
+        Self {
+            inner: Arc::new(Inner {
+                counter: Count::Shared(count),
+                ..Inner::new(previous + amount)
+            }),
+        }
+    }
+
+    /// Creates a counter whose decrements clamp at zero instead of wrapping.
+    pub(crate) fn new_saturating(count: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                saturating: true,
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Creates a counter whose count is never decremented by `fetch_sub`, so
+    /// it only ever grows as handles are added.
+    pub(crate) fn new_monotonic(count: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                monotonic: true,
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Creates a counter that closes the first time its count transitions to
+    /// zero, after which `try_fetch_add` always fails.
+    pub(crate) fn new_closeable(count: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                closeable: true,
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Creates a counter whose upgrades never take the count above `capacity`.
+    pub(crate) fn new_with_capacity(count: T, capacity: T) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                capacity: Some(capacity),
+                ..Inner::new(count)
+            }),
+        }
+    }
+
+    /// Adds `amount` to the count, returning the previous value.
+    ///
+    /// Panics in debug builds if the count overflows. This ignores whether the
+    /// counter is closed or at capacity, so it must only be used by handles
+    /// that already hold part of the count.
     #[inline]
-    pub(crate) fn fetch_add(&self, amount: usize) {
-        let count = self.counter.fetch_add(amount, Ordering::AcqRel);
-        if count + amount == 0 {
-            self.event.set();
-        } else if self.event.is_set() {
-            self.event.reset();
+    pub(crate) fn fetch_add(&self, amount: T) -> T {
+        let raw = T::fetch_add(&self.inner.counter, amount, Ordering::AcqRel);
+        let count = self.inner.value(raw);
+        self.added(amount, count + amount);
+        count
+    }
+
+    /// Adds `amount` to the count only if doing so would not overflow,
+    /// returning the previous value in either case. Like `fetch_add`, this
+    /// ignores whether the counter is closed or at capacity.
+    #[inline]
+    pub(crate) fn checked_fetch_add(&self, amount: T) -> Result<T, T> {
+        self.update_add(amount, self.inner.max(), |_| true)
+    }
+
+    /// Adds as much of `amount` as fits without overflowing, returning how
+    /// much was added.
+    pub(crate) fn saturating_fetch_add(&self, amount: T) -> T {
+        let max = self.inner.max();
+        let added = |count: T| amount.min(max - count);
+        let raw = T::fetch_update(
+            &self.inner.counter,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |raw| Some(raw + added(self.inner.value(raw))),
+        )
+        .unwrap();
+        let count = self.inner.value(raw);
+        let amount = added(count);
+        self.added(amount, count + amount);
+        amount
+    }
+
+    /// Adds `amount` to the count unless the counter is closed or the count
+    /// would exceed the capacity, returning the previous value in either case.
+    #[inline]
+    pub(crate) fn try_fetch_add(&self, amount: T) -> Result<T, T> {
+        self.update_add(amount, self.inner.limit(), |raw| !self.inner.closed(raw))
+    }
+
+    #[inline]
+    fn update_add(&self, amount: T, max: T, admit: impl Fn(T) -> bool) -> Result<T, T> {
+        // Upgrades of a nearly full counter all race for the same few slots,
+        // so back off between lost races instead of retrying right away.
+        let mut backoff = Backoff::new();
+        let mut raw = T::load(&self.inner.counter, Ordering::Acquire);
+        loop {
+            let count = self.inner.value(raw);
+            if !admit(raw) || count.checked_add(amount).is_none_or(|count| count > max) {
+                return Err(count);
+            }
+            match T::compare_exchange_weak(
+                &self.inner.counter,
+                raw,
+                raw + amount,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    self.added(amount, count + amount);
+                    return Ok(count);
+                }
+                Err(current) => {
+                    raw = current;
+                    backoff.snooze();
+                }
+            }
         }
     }
 
+    /// Subtracts `amount` from the count.
+    ///
+    /// The count must be at least `amount`, which holds as long as every
+    /// handle only subtracts what it previously added. Violating this panics
+    /// in debug builds and wraps the count in release builds, after which
+    /// `wait_for_empty` will never resolve. Saturating counters instead clamp
+    /// the count at zero. Does nothing for monotonic counters.
+    ///
+    /// Returns whether this drained the count.
     #[inline]
-    pub(crate) fn fetch_sub(&self, amount: usize) {
-        let count = self.counter.fetch_sub(amount, Ordering::AcqRel);
-        if count - amount == 0 {
-            self.event.set();
-        } else if self.event.is_set() {
-            self.event.reset();
+    pub(crate) fn fetch_sub(&self, amount: T) -> bool {
+        if self.inner.monotonic {
+            return false;
         }
+        self.sub(amount, self.inner.saturating)
+    }
+
+    /// Like `fetch_sub`, but always clamps at zero.
+    pub(crate) fn release(&self, amount: T) {
+        self.sub(amount, true);
+    }
+
+    fn sub(&self, amount: T, saturating: bool) -> bool {
+        let subtract = |count: T| {
+            if saturating {
+                count.saturating_sub(amount)
+            } else {
+                count.wrapping_sub(amount)
+            }
+        };
+
+        let count = if saturating || self.inner.closeable {
+            let raw = T::fetch_update(
+                &self.inner.counter,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                |raw| {
+                    let count = self.inner.value(raw);
+                    let remaining = subtract(count);
+                    if self.inner.closed(raw)
+                        || (self.inner.closeable && count != T::ZERO && remaining == T::ZERO)
+                    {
+                        Some(remaining | Inner::<T>::CLOSED)
+                    } else {
+                        Some(remaining)
+                    }
+                },
+            )
+            .unwrap();
+            self.inner.value(raw)
+        } else {
+            T::fetch_sub(&self.inner.counter, amount, Ordering::AcqRel)
+        };
+        debug_assert!(
+            saturating || count >= amount,
+            "counter underflowed: subtracted {} from {}",
+            amount,
+            count
+        );
+        self.subtracted(amount, count, subtract(count))
+    }
+
+    /// Subtracts `amount` from the count only if it is at least `amount`,
+    /// returning the previous value in either case.
+    pub(crate) fn try_fetch_sub(&self, amount: T) -> Result<T, T> {
+        let count = T::fetch_update(
+            &self.inner.counter,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |raw| {
+                self.inner.value(raw).checked_sub(amount)?;
+                Some(raw - amount)
+            },
+        )
+        .map(|raw| self.inner.value(raw))
+        .map_err(|raw| self.inner.value(raw))?;
+        self.subtracted(amount, count, count - amount);
+        Ok(count)
+    }
+
+    #[inline]
+    fn subtracted(&self, amount: T, count: T, remaining: T) -> bool {
+        #[cfg(not(any(feature = "tracing", feature = "diagnostics")))]
+        let _ = amount;
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            name = self.inner.name,
+            delta = %amount,
+            count = %remaining,
+            "counter decremented"
+        );
+        // Released so that `count_checked` seeing a subtraction also sees
+        // the addition the subtracting handle synchronized with.
+        #[cfg(feature = "diagnostics")]
+        {
+            let totals = &self.inner.totals;
+            let subbed = count - remaining;
+            totals.subbed.fetch_add(subbed.to_u64(), Ordering::Release);
+            if amount > subbed {
+                totals
+                    .clamped
+                    .fetch_add((amount - subbed).to_u64(), Ordering::Release);
+            }
+        }
+        self.decreased(count, remaining)
+    }
+
+    /// Overwrites the count with `count`, clearing the closed flag.
+    ///
+    /// Unlike every other update this doesn't preserve the invariant that the
+    /// count is the sum of the live handles' sizes.
+    pub(crate) fn reset_to(&self, count: T) {
+        let previous = self
+            .inner
+            .value(T::swap(&self.inner.counter, count, Ordering::AcqRel));
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            name = self.inner.name,
+            previous = %previous,
+            count = %count,
+            "counter reset"
+        );
+        self.reset(previous, count);
+    }
+
+    /// Like `reset_to`, but only if the count is `expected`, in the same
+    /// compare-and-swap. Returns the count found otherwise.
+    pub(crate) fn compare_and_reset(&self, expected: T, count: T) -> Result<(), T> {
+        let previous = T::fetch_update(
+            &self.inner.counter,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+            |raw| Some(count).filter(|_| self.inner.value(raw) == expected),
+        )
+        .map_err(|raw| self.inner.value(raw))?;
+        let previous = self.inner.value(previous);
+
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            name = self.inner.name,
+            previous = %previous,
+            count = %count,
+            "counter reset"
+        );
+        self.reset(previous, count);
+        Ok(())
+    }
+
+    #[inline]
+    fn reset(&self, previous: T, count: T) {
+        T::fetch_max(&self.inner.peak, count, Ordering::AcqRel);
+        self.decreased(previous, count);
+    }
+
+    /// Wakes waiters after the count went from `count` to `remaining`, and
+    /// runs `on_empty` if that drained it, returning whether it did.
+    #[inline]
+    fn decreased(&self, count: T, remaining: T) -> bool {
+        #[cfg(feature = "history")]
+        self.inner.history.record(remaining);
+        let drained = count != T::ZERO && remaining == T::ZERO;
+        #[cfg(all(feature = "tracing", debug_assertions))]
+        if drained {
+            self.inner.has_drained.store(true, Ordering::Relaxed);
+        }
+        #[cfg(feature = "std")]
+        if drained {
+            self.inner.events.drains.fetch_add(1, Ordering::AcqRel);
+        }
+        self.notify(remaining);
+        if drained {
+            if let Some(on_empty) = &self.inner.on_empty {
+                (on_empty.0)();
+            }
+        }
+        drained
+    }
+
+    /// The count itself, if this is the only handle to it and nothing else
+    /// can reach the atomic. Closeable counters are excluded since their raw
+    /// value carries the closed flag.
+    #[cfg(not(loom))]
+    pub(crate) fn get_mut(&mut self) -> Option<&mut T> {
+        let inner = Arc::get_mut(&mut self.inner)?;
+        if inner.closeable {
+            return None;
+        }
+        match &mut inner.counter {
+            Count::Owned(count) => Some(T::get_mut(&mut count.0)),
+            Count::Shared(count) => alloc::sync::Arc::get_mut(count).map(T::get_mut),
+            Count::Static(_) => None,
+        }
+    }
+
+    /// Whether both handles share the same underlying count.
+    #[inline]
+    pub(crate) fn ptr_eq(&self, other: &Counter<T>) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
+    /// The address of the shared state, identifying the underlying count.
+    #[inline]
+    pub(crate) fn addr(&self) -> usize {
+        Arc::as_ptr(&self.inner) as usize
+    }
+
+    /// Whether upgrades need to go through `try_fetch_add`.
+    #[inline]
+    pub(crate) fn restricts_upgrades(&self) -> bool {
+        self.inner.closeable || self.inner.capacity.is_some()
+    }
+
+    #[inline]
+    pub(crate) fn name(&self) -> Option<&'static str> {
+        self.inner.name
+    }
+
+    #[inline]
+    pub(crate) fn capacity(&self) -> Option<T> {
+        self.inner.capacity
+    }
+
+    #[inline]
+    fn added(&self, amount: T, count: T) {
+        #[cfg(feature = "history")]
+        self.inner.history.record(count);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(
+            name = self.inner.name,
+            delta = %amount,
+            count = %count,
+            "counter incremented"
+        );
+        #[cfg(not(any(feature = "tracing", feature = "diagnostics")))]
+        let _ = amount;
+        #[cfg(feature = "diagnostics")]
+        self.inner
+            .totals
+            .added
+            .fetch_add(amount.to_u64(), Ordering::Relaxed);
+
+        T::fetch_max(&self.inner.peak, count, Ordering::AcqRel);
+        self.notify(count);
+    }
+
+    /// Called by upgrades after adding `amount` to a count of `previous`.
+    /// Reviving a count that has drained before is usually a weak handle
+    /// upgrading after the work it tracked was considered done, so debug
+    /// builds with `tracing` warn about it.
+    #[inline]
+    pub(crate) fn upgraded(&self, _previous: T, _amount: T) {
+        #[cfg(all(feature = "tracing", debug_assertions))]
+        if _previous == T::ZERO
+            && _amount != T::ZERO
+            && self.inner.has_drained.load(Ordering::Relaxed)
+        {
+            tracing::warn!(
+                name = self.inner.name,
+                count = %_amount,
+                "counter revived after draining to zero"
+            );
+        }
+    }
+
+    #[inline]
+    fn notify(&self, _count: T) {
+        #[cfg(feature = "std")]
+        self.inner.events.notify(_count);
     }
 
     /// This method is inherently racey. Assume the count will have changed once
     /// the value is observed.
     #[inline]
-    pub(crate) fn get(&self) -> usize {
-        self.counter.load(Ordering::Acquire)
+    pub(crate) fn get(&self) -> T {
+        self.inner
+            .value(T::load(&self.inner.counter, Ordering::Acquire))
+    }
+
+    /// The atomic holding the raw count, which lives as long as `inner`.
+    #[inline]
+    pub(crate) fn as_ptr(&self) -> *const T::Atomic {
+        &*self.inner.counter
+    }
+
+    /// The `Arc` holding the raw count, for counts created with `from_arc`.
+    pub(crate) fn shared_arc(&self) -> Option<alloc::sync::Arc<T::Atomic>> {
+        match &self.inner.counter {
+            Count::Shared(count) => Some(alloc::sync::Arc::clone(count)),
+            Count::Owned(_) | Count::Static(_) => None,
+        }
+    }
+
+    /// Adds `amount` if the count is below `threshold`, in the same
+    /// compare-and-swap, returning the previous value either way. Respects the
+    /// capacity.
+    pub(crate) fn try_acquire_below(&self, threshold: T, amount: T) -> Result<T, T> {
+        let admit = |raw| !self.inner.closed(raw) && self.inner.value(raw) < threshold;
+        self.update_add(amount, self.inner.limit(), admit)
+    }
+
+    /// Closes a closeable counter without waiting for it to drain. Does
+    /// nothing for other counters.
+    #[cfg(feature = "std")]
+    pub(crate) fn close(&self) {
+        if self.inner.closeable {
+            T::fetch_update(
+                &self.inner.counter,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                |raw| Some(raw | Inner::<T>::CLOSED),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Whether a closeable counter has closed.
+    #[inline]
+    pub(crate) fn is_closed(&self) -> bool {
+        self.inner
+            .closed(T::load(&self.inner.counter, Ordering::Acquire))
+    }
+
+    /// Like `get`, but without synchronizing with the handles that changed
+    /// the count.
+    #[inline]
+    pub(crate) fn get_relaxed(&self) -> T {
+        self.inner
+            .value(T::load(&self.inner.counter, Ordering::Relaxed))
+    }
+
+    /// The highest count observed since creation or the last `reset_peak`.
+    #[inline]
+    pub(crate) fn peak(&self) -> T {
+        T::load(&self.inner.peak, Ordering::Acquire)
+    }
+
+    /// Restarts peak tracking from the current count.
+    #[inline]
+    pub(crate) fn reset_peak(&self) {
+        T::store(&self.inner.peak, self.get(), Ordering::Release);
+    }
+
+    #[inline]
+    pub(crate) fn add_weak(&self) {
+        self.inner.weak.fetch_add(1, Ordering::AcqRel);
+    }
+
+    #[inline]
+    pub(crate) fn sub_weak(&self) {
+        self.inner.weak.fetch_sub(1, Ordering::AcqRel);
+    }
+
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub(crate) fn weak_count(&self) -> usize {
+        self.inner.weak.load(Ordering::Acquire)
+    }
+
+    /// The lifetime totals added to and subtracted from the count.
+    #[cfg(feature = "diagnostics")]
+    #[inline]
+    pub(crate) fn lifetime_stats(&self) -> (u64, u64) {
+        let totals = &self.inner.totals;
+        (
+            totals.added.load(Ordering::Relaxed),
+            totals.subbed.load(Ordering::Relaxed),
+        )
+    }
+
+    /// The values the count last changed to, oldest first.
+    #[cfg(feature = "history")]
+    pub(crate) fn recent_counts(&self) -> Vec<T> {
+        self.inner.history.recent()
+    }
+
+    /// The count, unless more has been subtracted from it over its lifetime,
+    /// counting what saturating subtractions clamped away, than was added.
+    #[cfg(feature = "diagnostics")]
+    pub(crate) fn count_checked(&self) -> Result<T, crate::Desync> {
+        let totals = &self.inner.totals;
+        let count = self.get();
+        // Loading the subtractions first means every addition they depend on
+        // is visible, so a healthy counter is never reported.
+        let subtracted =
+            totals.subbed.load(Ordering::Acquire) + totals.clamped.load(Ordering::Acquire);
+        let added = totals.added.load(Ordering::Relaxed);
+        if subtracted > added {
+            Err(crate::Desync { added, subtracted })
+        } else {
+            Ok(count)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: CountInt> Counter<T> {
+    pub(crate) async fn wait_for_empty(&self) {
+        loop {
+            if self.get() == T::ZERO {
+                return;
+            }
+
+            // Register before re-checking so that a drain landing in between
+            // the two checks still wakes us up.
+            let listener = self.inner.events.empty.listen();
+            if self.get() == T::ZERO {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Like `wait_for_empty`, but a drain only wakes the longest waiting
+    /// waiter, which wakes the next one once it has seen the zero count.
+    pub(crate) async fn wait_for_empty_fair(&self) {
+        loop {
+            if self.get() == T::ZERO {
+                return;
+            }
+
+            // Dropping a listener that was notified in the meantime passes
+            // the notification on to the next waiter in line.
+            let listener = self.inner.events.empty_fair.listen();
+            if self.get() == T::ZERO {
+                return;
+            }
+
+            listener.await;
+            if self.get() == T::ZERO {
+                self.inner.events.empty_fair.notify(1);
+                return;
+            }
+        }
+    }
+
+    pub(crate) async fn wait_for_change(&self, since: T) -> T {
+        loop {
+            let count = self.get();
+            if count != since {
+                return count;
+            }
+
+            // Register before re-checking so that a change landing in between
+            // the two checks still wakes us up.
+            let listener = self.listen();
+            let count = self.get();
+            if count != since {
+                return count;
+            }
+
+            listener.await;
+        }
+    }
+
+    pub(crate) fn wait_for_empty_blocking(&self) {
+        let (lock, condvar) = &self.inner.events.blocking;
+        #[cfg(any(loom, not(feature = "parking_lot")))]
+        {
+            let mut guard = lock.lock().unwrap();
+            while self.get() != T::ZERO {
+                guard = condvar.wait(guard).unwrap();
+            }
+        }
+        #[cfg(all(not(loom), feature = "parking_lot"))]
+        {
+            let mut guard = lock.lock();
+            while self.get() != T::ZERO {
+                condvar.wait(&mut guard);
+            }
+        }
+    }
+
+    pub(crate) async fn wait_for_empty_timeout(&self, dur: Duration) -> bool {
+        if self.get() == T::ZERO {
+            return true;
+        }
+
+        timer::timeout(dur, self.wait_for_empty()).await.is_some()
+    }
+
+    pub(crate) async fn wait_for_count(&self, target: T) {
+        loop {
+            if self.get() <= target {
+                return;
+            }
+
+            // Register before re-checking so that a decrement landing in
+            // between the two checks still wakes us up.
+            let listener = self.listen();
+            if self.get() <= target {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    pub(crate) async fn wait_for_at_least(&self, n: T) {
+        loop {
+            if self.get() >= n {
+                return;
+            }
+
+            let listener = self.listen();
+            if self.get() >= n {
+                return;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Waits until `needed` more fits under the limit without adding it.
+    /// Returns `false` right away if it never could.
+    pub(crate) async fn wait_for_room(&self, needed: T) -> bool {
+        let limit = self.inner.limit();
+        if needed > limit {
+            return false;
+        }
+        let has_room = || limit.saturating_sub(self.get()) >= needed;
+        loop {
+            if has_room() {
+                return true;
+            }
+
+            let listener = self.listen();
+            if has_room() {
+                return true;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Waits until `try_fetch_add(amount)` succeeds, returning the previous
+    /// value. Dropping the future before it resolves leaves the count
+    /// untouched.
+    pub(crate) async fn acquire(&self, amount: T) -> T {
+        loop {
+            if let Ok(previous) = self.try_fetch_add(amount) {
+                return previous;
+            }
+
+            let listener = self.listen();
+            if let Ok(previous) = self.try_fetch_add(amount) {
+                return previous;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Waits until the count is below `threshold`, then adds `amount` in the
+    /// same compare-and-swap. Like `acquire`, this respects the capacity and
+    /// dropping the future before it resolves leaves the count untouched.
+    pub(crate) async fn acquire_below(&self, threshold: T, amount: T) -> T {
+        loop {
+            if let Ok(previous) = self.try_acquire_below(threshold, amount) {
+                return previous;
+            }
+
+            let listener = self.listen();
+            if let Ok(previous) = self.try_acquire_below(threshold, amount) {
+                return previous;
+            }
+
+            listener.await;
+        }
+    }
+
+    /// Returns a listener that is notified the next time the count changes.
+    ///
+    /// Check the count again after calling this, a change that happened
+    /// before the listener was created will not notify it.
+    pub(crate) fn listen(&self) -> EventListener {
+        self.inner.events.changed.listen()
+    }
+
+    /// The number of times the count has transitioned to zero.
+    #[inline]
+    pub(crate) fn drains(&self) -> usize {
+        self.inner.events.drains.load(Ordering::Acquire)
+    }
+
+    /// Returns a listener notified each time the count transitions to zero.
+    ///
+    /// Like `listen`, check `drains` again after calling this.
+    pub(crate) fn listen_empty(&self) -> EventListener {
+        self.inner.events.empty.listen()
     }
 }
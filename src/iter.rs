@@ -0,0 +1,79 @@
+use crate::{CountInt, GenericCounter, GenericWeakCounter};
+
+/// Extends iterators with counter based backpressure.
+pub trait CountedIteratorExt: Iterator + Sized {
+    /// Yields the items of self, each paired with a Counter of size 1, for as
+    /// long as the count is below `threshold` when the next item is pulled.
+    /// Holding on to the Counters is what eventually stops the iteration.
+    ///
+    /// The count is checked and incremented before an item is pulled, so no
+    /// item is consumed without a Counter to go with it. The check is
+    /// inherently racey: once this stops, other handles may drop the count
+    /// back below `threshold` right away. The returned iterator is fused and
+    /// doesn't resume when that happens.
+    ///
+    /// ```rust
+    /// use raii_counter_futures::{CountedIteratorExt, WeakCounter};
+    ///
+    /// let weak = WeakCounter::new();
+    /// let held: Vec<_> = (0..10).take_while_under(weak.clone(), 3).collect();
+    /// assert_eq!(held.len(), 3);
+    /// assert_eq!(weak.count(), 3);
+    /// ```
+    fn take_while_under<T: CountInt>(
+        self,
+        weak: GenericWeakCounter<T>,
+        threshold: T,
+    ) -> TakeWhileUnder<Self, T> {
+        TakeWhileUnder {
+            iter: self,
+            weak,
+            threshold,
+            done: false,
+        }
+    }
+}
+
+impl<I: Iterator> CountedIteratorExt for I {}
+
+/// Iterator returned by [`CountedIteratorExt::take_while_under`].
+#[derive(Debug)]
+#[must_use = "iterators are lazy and do nothing unless consumed"]
+pub struct TakeWhileUnder<I, T: CountInt = usize> {
+    iter: I,
+    weak: GenericWeakCounter<T>,
+    threshold: T,
+    done: bool,
+}
+
+impl<I: Iterator, T: CountInt> Iterator for TakeWhileUnder<I, T> {
+    type Item = (I::Item, GenericCounter<T>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.weak.counter.try_acquire_below(self.threshold, T::ONE) {
+            Ok(previous) => self.weak.counter.upgraded(previous, T::ONE),
+            Err(_) => {
+                self.done = true;
+                return None;
+            }
+        }
+
+        let counter = GenericCounter {
+            counter: self.weak.counter.clone(),
+            size: T::ONE,
+        };
+        // Dropping the Counter gives the slot back if iter is exhausted.
+        match self.iter.next() {
+            Some(item) => Some((item, counter)),
+            None => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<I: Iterator, T: CountInt> core::iter::FusedIterator for TakeWhileUnder<I, T> {}
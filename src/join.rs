@@ -0,0 +1,51 @@
+use crate::{CountInt, EmptyFuture, GenericWeakCounter};
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::task::Poll;
+
+/// Wait until every counter in `counters` has drained, waiting on all of them
+/// at once. Resolves immediately if `counters` is empty.
+///
+/// Each counter only has to be seen empty once: one that drains and is then
+/// upgraded again while the others are still draining doesn't hold this up.
+///
+/// ```rust
+/// use raii_counter_futures::{wait_for_all_empty, WeakCounter};
+///
+/// # futures::executor::block_on(async {
+/// let http = WeakCounter::new();
+/// let jobs = WeakCounter::new();
+/// let request = http.spawn_upgrade();
+/// std::thread::spawn(move || drop(request));
+/// wait_for_all_empty(&[http, jobs]).await;
+/// # });
+/// ```
+pub async fn wait_for_all_empty<T: CountInt>(counters: &[GenericWeakCounter<T>]) {
+    let mut waiting: Vec<EmptyFuture<T>> = counters.iter().map(|c| c.empty_future()).collect();
+    poll_fn(|cx| {
+        waiting.retain_mut(|future| Pin::new(future).poll(cx).is_pending());
+        if waiting.is_empty() {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+/// Wait until any counter in `counters` has drained, resolving to its index.
+/// If several are empty at once, the one that comes first in `counters` wins.
+/// Never resolves if `counters` is empty.
+pub async fn wait_for_any_empty<T: CountInt>(counters: &[GenericWeakCounter<T>]) -> usize {
+    let mut waiting: Vec<EmptyFuture<T>> = counters.iter().map(|c| c.empty_future()).collect();
+    poll_fn(|cx| {
+        match waiting
+            .iter_mut()
+            .position(|future| Pin::new(future).poll(cx).is_ready())
+        {
+            Some(index) => Poll::Ready(index),
+            None => Poll::Pending,
+        }
+    })
+    .await
+}
@@ -0,0 +1,49 @@
+use crate::internal;
+
+/// A one-shot latch that starts at `n` and completes once `count_down` has
+/// been called `n` times.
+///
+/// Clones share the same latch, so one can be handed to each task that
+/// counts down.
+///
+/// ```rust
+/// use raii_counter_futures::CountdownLatch;
+///
+/// # futures::executor::block_on(async {
+/// let latch = CountdownLatch::new(2);
+/// latch.count_down();
+/// latch.count_down();
+/// latch.wait().await;
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct CountdownLatch {
+    counter: internal::Counter,
+}
+
+impl CountdownLatch {
+    pub fn new(n: usize) -> CountdownLatch {
+        CountdownLatch {
+            counter: internal::Counter::new_saturating(n),
+        }
+    }
+
+    /// Count the latch down by one. Counting down a latch that has already
+    /// completed does nothing.
+    pub fn count_down(&self) {
+        self.counter.fetch_sub(1);
+    }
+
+    /// The number of `count_down` calls remaining. This method is inherently
+    /// racey, see `WeakCounter::count`.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.counter.get()
+    }
+
+    /// Wait until the latch has been counted down to zero. Resolves
+    /// immediately if it already has.
+    pub async fn wait(&self) {
+        self.counter.wait_for_empty().await
+    }
+}
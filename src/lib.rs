@@ -25,191 +25,3692 @@
 //!
 //! assert_eq!(weak.count(), 0);
 //! ```
+//!
+//! ## Count width
+//!
+//! `Counter` and `WeakCounter` keep their count in a `usize`. Where that is
+//! too small, e.g. for byte counts on 32-bit targets, `Counter64` and
+//! `WeakCounter64` keep it in a `u64` instead, and `Counter32` and
+//! `WeakCounter32` keep it in a `u32` where memory is tight. All of them are
+//! aliases of `GenericCounter` and `GenericWeakCounter`, which are generic
+//! over any `CountInt`.
+//!
+//! ## Features
+//!
+//! - `std` (default): the async, blocking and stream based waits, `Observer`,
+//!   `CountdownLatch`, `WaitGroup` and `CounterPool`. Without it the crate is `no_std` and
+//!   only depends on `alloc`.
+//! - `diagnostics`: tracks lifetime totals of the count, see `lifetime_stats`.
+//! - `history`: keeps the last few values of the count in a ring buffer, see
+//!   `recent_counts`.
+//! - `padded`: aligns each count to its own cache line to avoid false sharing.
+//! - `parking_lot`: backs `wait_for_empty_blocking` with `parking_lot`'s
+//!   `Mutex` and `Condvar` instead of `std`'s. Only has an effect with `std`.
+//! - `tokio-timer`: times out the `*_timeout` waits with `tokio::time`, which
+//!   requires them to run inside a tokio runtime with time enabled.
+//! - `async-std-timer`: times them out with `async_std::future::timeout`.
+//!   `tokio-timer` takes precedence if both are enabled. Without either, the
+//!   `*_timeout` waits are timed out by one background thread shared by all
+//!   of them, spawned the first time one is called.
+//! - `prometheus`: `register_gauge` exposes a count as a Prometheus gauge.
+//! - `serde`: implements `Serialize` for a snapshot of the count.
+//! - `tracing`: emits a `trace!` event with the delta and new count on every
+//!   increment and decrement.
 
-use std::fmt::{self, Display, Formatter};
+#![cfg_attr(not(feature = "std"), no_std)]
 
+extern crate alloc;
+
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use core::fmt::{self, Debug, Display, Formatter};
+#[cfg(feature = "std")]
+use core::future::poll_fn;
+use core::future::Future;
+use core::hash::{Hash, Hasher};
+use core::ops::{Add, AddAssign, SubAssign};
+#[cfg(feature = "std")]
+use core::pin::pin;
+#[cfg(feature = "std")]
+use core::task::Poll;
+#[cfg(feature = "std")]
+use core::time::Duration;
+#[cfg(feature = "std")]
+use futures_core::Stream;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+mod atomic_counter;
+mod backoff;
+mod batch;
+mod budget;
+mod builder;
+#[cfg(all(
+    feature = "std",
+    not(any(feature = "tokio-timer", feature = "async-std-timer"))
+))]
+mod delay;
+mod either;
+mod error;
+#[cfg(feature = "std")]
+mod future;
+mod int;
 mod internal;
+mod iter;
+#[cfg(feature = "std")]
+mod join;
+#[cfg(feature = "std")]
+mod latch;
+#[cfg(feature = "prometheus")]
+mod metrics;
+#[cfg(feature = "std")]
+mod observer;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "serde")]
+mod serialize;
+#[cfg(feature = "std")]
+mod stream;
+mod sync;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(feature = "std")]
+mod wait_group;
+mod weak_ref;
+
+pub use atomic_counter::AtomicCounter;
+pub use batch::CounterBatch;
+pub use budget::{Budget, BudgetGuard};
+pub use builder::CounterBuilder;
+pub use either::Either;
+pub use error::{CounterOverflow, Desync};
+#[cfg(feature = "std")]
+pub use future::EmptyFuture;
+pub use int::CountInt;
+pub use iter::{CountedIteratorExt, TakeWhileUnder};
+#[cfg(feature = "std")]
+pub use join::{wait_for_all_empty, wait_for_any_empty};
+#[cfg(feature = "std")]
+pub use latch::CountdownLatch;
+#[cfg(feature = "prometheus")]
+pub use metrics::register_gauge;
+#[cfg(feature = "std")]
+pub use observer::Observer;
+#[cfg(feature = "std")]
+pub use pool::{CounterPool, PooledCounter};
+#[cfg(feature = "std")]
+pub use wait_group::{WaitGroup, WaitGroupGuard};
+pub use weak_ref::WeakCounterRef;
 
 /// Essentially an AtomicUsize that is clonable and whose count is based
 /// on the number of copies. The count is automatically updated on Drop.
-#[derive(Debug)]
-pub struct Counter {
-    counter: internal::Counter,
-    size: usize,
+///
+/// Generic over the integer type `T` the count is kept in, see the `Counter`
+/// and `Counter64` aliases.
+///
+/// Every handle to a count shares one allocation, made when the count is
+/// created. Upgrading, cloning and dropping Counters after that never
+/// allocate, they only update the count and a reference count. `CounterPool`
+/// recycles the handles to skip the reference count as well.
+///
+/// Dropping a Counter releases its share of the count right away, so one
+/// that isn't bound to a variable is almost always a bug:
+///
+/// ```compile_fail
+/// #![deny(unused_must_use)]
+/// use raii_counter_futures::WeakCounter;
+///
+/// let weak = WeakCounter::new();
+/// weak.spawn_upgrade(); // Released before the next line runs.
+/// ```
+#[must_use = "dropping the Counter immediately releases the count; bind it to a variable"]
+pub struct GenericCounter<T: CountInt> {
+    counter: internal::Counter<T>,
+    size: T,
 }
 
 /// A 'weak' Counter that does not affect the count.
-#[derive(Clone, Debug)]
-pub struct WeakCounter {
-    counter: internal::Counter,
+///
+/// Generic over the integer type `T` the count is kept in, see the
+/// `WeakCounter` and `WeakCounter64` aliases.
+pub struct GenericWeakCounter<T: CountInt> {
+    counter: internal::Counter<T>,
 }
 
+/// A Counter whose count is a `usize`.
+pub type Counter = GenericCounter<usize>;
+
+/// A WeakCounter whose count is a `usize`.
+pub type WeakCounter = GenericWeakCounter<usize>;
+
+/// A Counter whose count is a `u64` regardless of the pointer width, e.g. for
+/// byte counts that may exceed 4 GiB on 32-bit targets.
+#[cfg(target_has_atomic = "64")]
+pub type Counter64 = GenericCounter<u64>;
+
+/// A WeakCounter whose count is a `u64`, see `Counter64`.
+#[cfg(target_has_atomic = "64")]
+pub type WeakCounter64 = GenericWeakCounter<u64>;
+
+/// A Counter whose count is a `u32`, halving the size of the count on 64-bit
+/// targets where memory is tight.
+#[cfg(target_has_atomic = "32")]
+pub type Counter32 = GenericCounter<u32>;
+
+/// A WeakCounter whose count is a `u32`, see `Counter32`.
+#[cfg(target_has_atomic = "32")]
+pub type WeakCounter32 = GenericWeakCounter<u32>;
+
 impl Counter {
-    pub fn new() -> Counter {
-        Counter::new_with_size(1)
+    /// Start configuring a new count, see `CounterBuilder`
+    pub fn builder() -> CounterBuilder {
+        CounterBuilder::new()
+    }
+}
+
+impl<T: CountInt> GenericCounter<T> {
+    pub fn new() -> GenericCounter<T> {
+        GenericCounter::new_with_size(T::ONE)
+    }
+
+    /// Create a Counter of size 1 whose handles never subtract from the count,
+    /// for counting how many were ever created rather than how many are
+    /// alive. Dropping, downgrading or shrinking a Counter of this count
+    /// leaves it unchanged, so it only ever grows and `wait_for_empty` never
+    /// resolves. `WeakCounter::release` and `reset_to` still apply.
+    pub fn new_monotonic() -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::new_monotonic(T::ONE),
+            size: T::ONE,
+        }
+    }
+
+    /// Create a Counter of size 1 along with a WeakCounter observing the same
+    /// count
+    pub fn new_paired() -> (GenericCounter<T>, GenericWeakCounter<T>) {
+        let counter = GenericCounter::new();
+        let weak = counter.downgrade_ref();
+        (counter, weak)
+    }
+
+    pub fn new_with_size(size: T) -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::new(size),
+            size,
+        }
+    }
+
+    /// Like `new_with_size`, but every handle sharing this count subtracts
+    /// at most the current value on `Drop`, clamping the count at zero
+    /// instead of wrapping around if sizes ever get mismatched.
+    pub fn new_saturating(size: T) -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::new_saturating(size),
+            size,
+        }
+    }
+
+    /// Like `new_with_size`, but `on_empty` is invoked every time the count
+    /// transitions to zero, on the thread that dropped the final handle.
+    ///
+    /// The count may bounce back up after draining (e.g. through a
+    /// WeakCounter), in which case `on_empty` fires again on the next drain.
+    /// Capturing a WeakCounter of this count inside `on_empty` creates a
+    /// reference cycle that keeps the count allocated forever.
+    ///
+    /// `on_empty` must be `Send + Sync` because any thread holding a handle
+    /// may be the one to drain the count, which is also what keeps Counter
+    /// and WeakCounter `Send + Sync`.
+    pub fn new_with_on_empty(
+        size: T,
+        on_empty: impl Fn() + Send + Sync + 'static,
+    ) -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::new_with_on_empty(size, internal::OnEmpty::new(on_empty)),
+            size,
+        }
+    }
+
+    /// Adopt an atomic shared with other code as the count, adding `size` to
+    /// it. Handles to the returned Counter update `count` directly, and
+    /// changes made through `count` show up in their `count`.
+    ///
+    /// Changes that bypass the handles bypass the drain event too, so waiters
+    /// and `on_empty` only notice the count reach zero when a handle is the one
+    /// to drain it. Subtracting more than the external code added underflows
+    /// the count once the handles drop.
+    ///
+    /// ```rust
+    /// use raii_counter_futures::Counter;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use std::sync::Arc;
+    ///
+    /// let count = Arc::new(AtomicUsize::new(2));
+    /// let counter = Counter::from_arc(Arc::clone(&count), 1);
+    /// assert_eq!(counter.count(), 3);
+    /// ```
+    pub fn from_arc(count: Arc<T::Atomic>, size: T) -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::from_arc(count, size),
+            size,
+        }
+    }
+
+    /// Like `new_with_on_empty`, but `last_drop` runs only once, the first
+    /// time the count drains, for tearing something down once every holder
+    /// is gone. Later drains after the count bounces back up don't run it
+    /// again. Only available with `std`.
+    #[cfg(feature = "std")]
+    pub fn new_with_last_drop(
+        size: T,
+        last_drop: impl FnOnce() + Send + 'static,
+    ) -> GenericCounter<T> {
+        GenericCounter {
+            counter: internal::Counter::new_with_on_empty(size, internal::OnEmpty::once(last_drop)),
+            size,
+        }
     }
 
-    pub fn new_with_size(size: usize) -> Counter {
-        Counter {
-            counter: internal::Counter::new(1),
+    /// Like `new_with_last_drop`, but the teardown is async, so it can't run
+    /// from `Drop`. Instead it is awaited by the `release_async` call that
+    /// first drains the count. Only available with `std`.
+    ///
+    /// Dropping Counters still releases the count as usual, but never runs
+    /// the teardown, so a drain caused by a plain drop leaves it for a later
+    /// drain through `release_async`.
+    #[cfg(feature = "std")]
+    pub fn new_with_async_last_drop<F>(
+        size: T,
+        last_drop: impl FnOnce() -> F + Send + 'static,
+    ) -> GenericCounter<T>
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        GenericCounter {
+            counter: internal::Counter::new_with_async_last_drop(
+                size,
+                internal::AsyncLastDrop::new(last_drop),
+            ),
             size,
         }
     }
 
     /// Consume self (causing the count to decrease by 1)
     /// and return a weak reference to the count through a WeakCounter
-    pub fn downgrade(self) -> WeakCounter {
-        WeakCounter {
+    pub fn downgrade(self) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(self.counter.clone())
+    }
+
+    /// Borrow a view of the count from self, which unlike `downgrade_ref`
+    /// doesn't clone the handle, e.g. to pass to a short-lived helper
+    pub fn as_weak(&self) -> WeakCounterRef<'_, T> {
+        WeakCounterRef::new(&self.counter)
+    }
+
+    /// Return a weak reference to the count through a WeakCounter without
+    /// consuming self, leaving the count untouched
+    pub fn downgrade_ref(&self) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(self.counter.clone())
+    }
+
+    /// Consume self without decrementing the count, so its `size` stays part
+    /// of the count forever and the count can never drain below it.
+    ///
+    /// Unlike `mem::forget`, this still releases self's reference to the
+    /// shared state, which is freed as usual once every other handle is gone.
+    pub fn leak(mut self) {
+        self.size = T::ZERO;
+    }
+
+    /// Like `downgrade`, but leaves self's `size` in the count like `leak`,
+    /// handing the contribution over to whoever releases it manually.
+    ///
+    /// Nothing subtracts the size automatically anymore, so the count can't
+    /// drain until it is given back through `WeakCounter::release`.
+    pub fn downgrade_keep_count(self) -> GenericWeakCounter<T> {
+        let weak = self.downgrade_ref();
+        self.leak();
+        weak
+    }
+
+    /// Clone self, failing instead of overflowing if the count would exceed
+    /// `T::MAX`. The count is left untouched on failure.
+    pub fn try_clone(&self) -> Result<GenericCounter<T>, CounterOverflow> {
+        self.counter
+            .checked_fetch_add(self.size)
+            .map_err(|_| CounterOverflow)?;
+        Ok(GenericCounter {
+            counter: self.counter.clone(),
+            size: self.size,
+        })
+    }
+
+    /// Create another Counter of the same count, but of `size` instead of
+    /// self's size. Like `clone`, this ignores the capacity and panics in
+    /// debug builds if the count overflows.
+    pub fn clone_with_size(&self, size: T) -> GenericCounter<T> {
+        self.counter.fetch_add(size);
+        GenericCounter {
+            counter: self.counter.clone(),
+            size,
+        }
+    }
+
+    /// Split self into `parts` Counters of the same count whose sizes add up
+    /// to self's size, leaving the count unchanged. The sizes differ by at
+    /// most one, with the larger ones first, and are zero if there are more
+    /// parts than self's size.
+    ///
+    /// Panics if `parts` is zero.
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn split(mut self, parts: usize) -> Vec<GenericCounter<T>> {
+        assert!(parts != 0, "split a Counter into zero parts");
+        let (size, remainder) = self.size.div_rem_usize(parts);
+        let counters = (0..parts)
+            .map(|i| GenericCounter {
+                counter: self.counter.clone(),
+                size: if (i as u64) < remainder.to_u64() {
+                    size + T::ONE
+                } else {
+                    size
+                },
+            })
+            .collect();
+        // The pieces now hold self's contribution.
+        self.size = T::ZERO;
+        counters
+    }
+
+    /// Combine self and `other` into a single Counter whose size is the sum
+    /// of theirs, leaving the count unchanged. The inverse of `split`.
+    ///
+    /// Returns both back unchanged if they don't share the same count, see
+    /// `same_counter`.
+    pub fn merge(
+        mut self,
+        mut other: GenericCounter<T>,
+    ) -> Result<GenericCounter<T>, (GenericCounter<T>, GenericCounter<T>)> {
+        if !self.same_counter(&other) {
+            return Err((self, other));
+        }
+        // Both sizes are part of the count, so their sum can't overflow.
+        self.size = self.size + other.size;
+        other.size = T::ZERO;
+        Ok(self)
+    }
+
+    /// Clone self, adding only as much of `size` as fits below `T::MAX`
+    /// instead of overflowing. The clone's size is what was actually added,
+    /// possibly zero, so dropping it restores the count exactly.
+    pub fn saturating_clone(&self) -> GenericCounter<T> {
+        GenericCounter {
+            size: self.counter.saturating_fetch_add(self.size),
             counter: self.counter.clone(),
         }
     }
 
+    /// Create `n` clones of self, adding to the count once for the whole
+    /// batch rather than once per clone.
+    ///
+    /// Panics if `n` clones of this size would overflow the count.
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn clone_many(&self, n: usize) -> Vec<GenericCounter<T>> {
+        let amount = self
+            .size
+            .checked_mul_usize(n)
+            .expect("clone_many overflowed the count");
+        self.counter
+            .checked_fetch_add(amount)
+            .expect("clone_many overflowed the count");
+        (0..n)
+            .map(|_| GenericCounter {
+                counter: self.counter.clone(),
+                size: self.size,
+            })
+            .collect()
+    }
+
     /// This method is inherently racey. Assume the count will have changed once
     /// the value is observed.
     #[inline]
-    pub fn count(&self) -> usize {
+    pub fn count(&self) -> T {
         self.counter.get()
     }
 
+    /// Like `count`, but loads with `Ordering::Relaxed`, so the value may be
+    /// even staler and says nothing about what the handles did before
+    /// changing it. Meant for cheap, high frequency sampling in metrics.
+    #[inline]
+    pub fn count_relaxed(&self) -> T {
+        self.counter.get_relaxed()
+    }
+
+    /// A pointer to the atomic the count is stored in, e.g. for non-Rust code
+    /// to poll the count through FFI.
+    ///
+    /// The pointer stays valid for as long as any Counter or WeakCounter of
+    /// this count is alive, and must only be used for loads: changing the
+    /// count through it desyncs the count from the handles. A closeable
+    /// counter keeps its closed flag in the most significant bit of the raw
+    /// value.
+    #[inline]
+    pub fn as_raw_atomic(&self) -> *const T::Atomic {
+        self.counter.as_ptr()
+    }
+
+    /// Whether the count is currently zero.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count() == T::ZERO
+    }
+
+    /// The count minus self's own `size`, i.e. what the other handles hold.
+    ///
+    /// This method is inherently racey. Another clone may be created right
+    /// after the value is observed.
+    #[inline]
+    pub fn count_excluding_self(&self) -> T {
+        self.count().saturating_sub(self.size)
+    }
+
+    /// Whether self is the only contribution to the count, e.g. to run
+    /// cleanup in the last holder.
+    ///
+    /// This method is inherently racey. Another clone may be created right
+    /// after this returns `true`, e.g. by upgrading a WeakCounter.
+    #[inline]
+    pub fn is_last(&self) -> bool {
+        self.count() == self.size
+    }
+
+    /// The number of WeakCounters observing this count.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn weak_count(&self) -> usize {
+        self.counter.weak_count()
+    }
+
+    /// The lifetime totals `(added, subtracted)` of the count, where the
+    /// initial count counts as added. The current count is always their
+    /// difference, so comparing them with what was expected helps track down
+    /// leaked clones or double drops.
+    ///
+    /// Only available with the `diagnostics` feature. The totals are loaded
+    /// with `Ordering::Relaxed` and may lag the count slightly.
+    #[cfg(feature = "diagnostics")]
+    #[inline]
+    pub fn lifetime_stats(&self) -> (u64, u64) {
+        self.counter.lifetime_stats()
+    }
+
+    /// The last values the count changed to, oldest first, starting with its
+    /// initial value. Up to 32 are kept, older ones are overwritten.
+    ///
+    /// Only available with the `history` feature. Samples are recorded right
+    /// after each change without a lock, so concurrent changes may be
+    /// recorded out of order, and a sample being written while this runs may
+    /// be missing or stale.
+    #[cfg(feature = "history")]
+    pub fn recent_counts(&self) -> Vec<T> {
+        self.counter.recent_counts()
+    }
+
+    /// The highest count observed since the counter was created or since the
+    /// last call to `reset_peak`.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn peak(&self) -> T {
+        self.counter.peak()
+    }
+
+    /// Restart peak tracking from the current count
+    #[inline]
+    pub fn reset_peak(&self) {
+        self.counter.reset_peak();
+    }
+
+    /// A mutable reference to the count, if self is the only handle to it,
+    /// following `Arc::get_mut`. Returns `None` while any other Counter,
+    /// WeakCounter or future waiting on the count is alive, and always for
+    /// counters created with `from_static` or closeable ones. Since nothing
+    /// else can observe the count, it can be changed without atomics.
+    ///
+    /// Nothing is woken and `on_empty` doesn't run; there is no one else to
+    /// tell. Writes also bypass the bookkeeping done on other changes: the
+    /// `peak` isn't raised, the `lifetime_stats` totals behind `count_checked`
+    /// don't include them, and `recent_counts` doesn't record them. Self
+    /// still subtracts its size on `Drop`, so leaving the count below it
+    /// underflows like `WeakCounter::reset_to` would.
+    #[cfg(not(loom))]
+    pub fn get_mut(&mut self) -> Option<&mut T> {
+        self.counter.get_mut()
+    }
+
+    /// Change the amount this Counter contributes to the count, adjusting the
+    /// count by the difference.
+    ///
+    /// Since self already holds `size` of the count, shrinking can never take
+    /// the count below zero. Like `clone`, growing ignores any capacity.
+    pub fn set_size(&mut self, new_size: T) {
+        if new_size > self.size {
+            self.counter.fetch_add(new_size - self.size);
+        } else if new_size < self.size {
+            self.counter.fetch_sub(self.size - new_size);
+        }
+        self.size = new_size;
+    }
+
+    /// Whether `other` shares the same underlying count as self. This compares
+    /// identity, not the current count.
+    #[inline]
+    pub fn same_counter(&self, other: &GenericCounter<T>) -> bool {
+        self.counter.ptr_eq(&other.counter)
+    }
+
+    /// Whether `other` observes the same underlying count as self
+    #[inline]
+    pub fn same_counter_as(&self, other: &GenericWeakCounter<T>) -> bool {
+        self.counter.ptr_eq(&other.counter)
+    }
+
+    /// The name of the underlying count, if any
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        self.counter.name()
+    }
+
+    /// The amount this Counter contributes to the count
+    #[inline]
+    pub fn size(&self) -> T {
+        self.size
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: CountInt> GenericCounter<T> {
+    /// Release self like dropping it would, then, if that drained the count,
+    /// await the teardown registered with `new_with_async_last_drop` before
+    /// returning. An explicit await point for async cleanup on the last
+    /// release, which `Drop` can't provide.
+    ///
+    /// Resolves right after releasing if the count didn't drain, if there is
+    /// no teardown, or if it has already run.
+    pub async fn release_async(mut self) {
+        let size = self.size;
+        // Released here rather than by `Drop`.
+        self.size = T::ZERO;
+        if size != T::ZERO && self.counter.fetch_sub(size) {
+            if let Some(last_drop) = self.counter.take_async_last_drop() {
+                last_drop().await;
+            }
+        }
+    }
+
     /// Returns a future that waits until the counter contains a 0 value
+    ///
+    /// The future is cancellation safe: dropping it before it resolves leaves
+    /// other waiters unaffected, and every waiter registered before a drain
+    /// is woken by it. A waiter only resolves once it sees the zero count
+    /// though, so if the count is raised again before the waiter gets to run
+    /// it keeps waiting for the next drain.
     pub async fn wait_for_empty(&self) {
         self.counter.wait_for_empty().await;
     }
+
+    /// Like `wait_for_empty`, but resolves to how long it waited, measured
+    /// with the monotonic `Instant` clock from when the future is first
+    /// polled. Resolves to a near-zero `Duration` if the counter is already
+    /// empty.
+    pub async fn wait_for_empty_timed(&self) -> Duration {
+        let start = Instant::now();
+        self.counter.wait_for_empty().await;
+        start.elapsed()
+    }
+
+    /// Like `wait_for_empty`, but instead of waking every waiter at once, a
+    /// drain wakes the one that has been waiting the longest, which wakes the
+    /// next once it has seen the zero count. This avoids a thundering herd of
+    /// waiters re-checking a count that bounces off zero, at the cost of
+    /// waking them in turn when it doesn't.
+    ///
+    /// A woken waiter that finds the count raised again goes to the back of
+    /// the line. Waiters must be polled to pass the wakeup on, so one that is
+    /// woken but never polled again (without being dropped) holds up the
+    /// waiters behind it.
+    ///
+    /// This is not faster: in the `thundering_herd` benchmark both waits take
+    /// about as long to resolve. Use it when waiters should finish in the
+    /// order they started waiting, or when waking one costs enough that a
+    /// single wakeup per drain matters.
+    pub async fn wait_for_empty_fair(&self) {
+        self.counter.wait_for_empty_fair().await;
+    }
+
+    /// Like `wait_for_empty`, but returns a named future that can be stored
+    /// without boxing. The future is `Send`, `Unpin`, and holds on to the
+    /// count itself, so it can outlive self.
+    pub fn empty_future(&self) -> EmptyFuture<T> {
+        EmptyFuture::new(self.counter.clone())
+    }
+
+    /// Blocks the current thread until the counter contains a 0 value
+    pub fn wait_for_empty_blocking(&self) {
+        self.counter.wait_for_empty_blocking();
+    }
+
+    /// Returns a future that waits until the counter contains a 0 value or
+    /// `dur` has elapsed, whichever comes first. Resolves to `true` if the
+    /// counter drained in time and `false` on timeout.
+    pub async fn wait_for_empty_timeout(&self, dur: Duration) -> bool {
+        self.counter.wait_for_empty_timeout(dur).await
+    }
+
+    /// Returns a future that waits until the counter contains a value less
+    /// than or equal to `target`
+    pub async fn wait_for_count(&self, target: T) {
+        self.counter.wait_for_count(target).await;
+    }
 }
 
-impl Clone for Counter {
+impl<T: CountInt> Clone for GenericCounter<T> {
+    /// Panics in debug builds if the count overflows `T`, see
+    /// [`GenericCounter::try_clone`] for a fallible alternative.
     fn clone(&self) -> Self {
-        self.counter.fetch_add(self.size);
-        Counter {
-            counter: self.counter.clone(),
-            size: self.size,
-        }
+        self.clone_with_size(self.size)
+    }
+}
+
+impl<T: CountInt> Default for GenericCounter<T> {
+    fn default() -> Self {
+        GenericCounter::new()
     }
 }
 
-impl Display for Counter {
+impl<T: CountInt> Debug for GenericCounter<T> {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "Counter(count={})", self.count())
+        f.debug_struct("Counter")
+            .field("count", &self.count())
+            .field("size", &self.size)
+            .field("capacity", &self.counter.capacity())
+            .field("name", &self.name())
+            .finish()
     }
 }
 
-impl Drop for Counter {
-    fn drop(&mut self) {
-        self.counter.fetch_sub(self.size);
+impl<T: CountInt> Display for GenericCounter<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let count = DisplayCount(&self.counter);
+        match self.name() {
+            Some(name) => write!(
+                f,
+                "Counter(name={}, count={}, size={})",
+                name, count, self.size
+            ),
+            None => write!(f, "Counter(count={}, size={})", count, self.size),
+        }
     }
 }
 
-impl WeakCounter {
-    pub fn new() -> WeakCounter {
-        WeakCounter {
-            counter: internal::Counter::new(0),
+/// Shows the count as `count/capacity` for counters with a capacity, marked
+/// once it is full so rejected upgrades are easy to spot in logs.
+struct DisplayCount<'a, T: CountInt>(&'a internal::Counter<T>);
+
+impl<T: CountInt> Display for DisplayCount<'_, T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let count = self.0.get();
+        match self.0.capacity() {
+            Some(capacity) if count >= capacity => write!(f, "{}/{} full", count, capacity),
+            Some(capacity) => write!(f, "{}/{}", count, capacity),
+            None => write!(f, "{}", count),
         }
     }
+}
 
-    /// This method is inherently racey. Assume the count will have changed once
-    /// the value is observed.
-    #[inline]
-    pub fn count(&self) -> usize {
-        self.counter.get()
+/// Grows the size of the Counter by `rhs` like `set_size`, adding `rhs` to the
+/// count. Panics in debug builds if the size or the count overflows.
+impl<T: CountInt> AddAssign<T> for GenericCounter<T> {
+    fn add_assign(&mut self, rhs: T) {
+        self.set_size(self.size + rhs);
     }
+}
 
-    /// Consumes self, becomes a Counter
-    pub fn upgrade(self) -> Counter {
-        self.spawn_upgrade()
+/// Shrinks the size of the Counter by `rhs` like `set_size`, subtracting `rhs`
+/// from the count. Panics if `rhs` is larger than the size, since the Counter
+/// can't give up more than it holds.
+impl<T: CountInt> SubAssign<T> for GenericCounter<T> {
+    fn sub_assign(&mut self, rhs: T) {
+        let size = self
+            .size
+            .checked_sub(rhs)
+            .expect("subtracted more than the Counter's size");
+        self.set_size(size);
     }
+}
 
-    /// Instead of clone + upgrade, this will only clone once
-    /// Defaults to a Counter of size 1
-    pub fn spawn_upgrade(&self) -> Counter {
-        self.spawn_upgrade_with_size(1)
+/// Consumes the Counter and returns it grown by `rhs`, see `AddAssign`.
+impl<T: CountInt> Add<T> for GenericCounter<T> {
+    type Output = GenericCounter<T>;
+
+    fn add(mut self, rhs: T) -> GenericCounter<T> {
+        self += rhs;
+        self
     }
+}
 
-    /// Instead of clone + upgrade, this will only clone once
-    pub fn spawn_upgrade_with_size(&self, size: usize) -> Counter {
-        self.counter.fetch_add(size);
-        Counter {
-            counter: self.counter.clone(),
-            size,
-        }
+impl<T: CountInt> From<GenericCounter<T>> for GenericWeakCounter<T> {
+    fn from(counter: GenericCounter<T>) -> GenericWeakCounter<T> {
+        counter.downgrade()
     }
+}
 
-    /// Returns a future that waits until the counter contains a 0 value
-    pub async fn wait_for_empty(&self) {
-        self.counter.wait_for_empty().await;
+/// Upgrades like `try_upgrade`, handing the WeakCounter back if the counter
+/// is closed or full.
+impl<T: CountInt> TryFrom<GenericWeakCounter<T>> for GenericCounter<T> {
+    type Error = GenericWeakCounter<T>;
+
+    fn try_from(weak: GenericWeakCounter<T>) -> Result<GenericCounter<T>, GenericWeakCounter<T>> {
+        weak.try_spawn_upgrade().ok_or(weak)
     }
 }
 
-impl Display for WeakCounter {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(f, "WeakCounter(count={})", self.count())
+impl<T: CountInt> Drop for GenericCounter<T> {
+    fn drop(&mut self) {
+        // A Counter of size zero, e.g. one that was leaked, holds nothing.
+        if self.size != T::ZERO {
+            self.counter.fetch_sub(self.size);
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::time::{Duration, Instant};
-    use tokio::time::delay_for;
+impl<T: CountInt> GenericWeakCounter<T> {
+    pub fn new() -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::new(T::ZERO))
+    }
 
-    #[test]
-    fn it_works() {
-        let counter = Counter::new();
-        assert_eq!(counter.count(), 1);
+    /// Create a WeakCounter whose count starts at `count` instead of zero,
+    /// e.g. to reconstruct state where `count` holds are known to be
+    /// outstanding without Counters to go with them. Release them with
+    /// `release`, like the holds left by `downgrade_keep_count`.
+    ///
+    /// Waiters only ever check the count itself, so `wait_for_empty` waits
+    /// for those holds to be released unless `count` is zero.
+    pub fn new_with_count(count: T) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::new(count))
+    }
 
-        let weak = counter.downgrade();
-        assert_eq!(weak.count(), 0);
+    /// Create a WeakCounter with a name attached, which the `tracing` feature
+    /// includes in its events to tell counters apart
+    pub fn new_named(name: &'static str) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::new_named(T::ZERO, name))
+    }
 
-        {
-            let _counter1 = weak.spawn_upgrade();
-            assert_eq!(weak.count(), 1);
-            let _counter2 = weak.spawn_upgrade();
-            assert_eq!(weak.count(), 2);
-        }
+    /// Create a WeakCounter that closes the first time its count drains to
+    /// zero. Once closed, `try_upgrade` and `try_spawn_upgrade` return `None`
+    /// and the infallible upgrades panic, so a closed counter can never be
+    /// revived. A new closeable counter starts at zero without being closed.
+    pub fn new_closeable() -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::new_closeable(T::ZERO))
+    }
 
-        assert_eq!(weak.count(), 0);
+    /// Create a WeakCounter whose upgrades never take the count above
+    /// `capacity`. Once full, `try_spawn_upgrade` returns `None` and the
+    /// infallible upgrades panic. Cloning an existing Counter is not limited.
+    pub fn new_with_capacity(capacity: T) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::new_with_capacity(T::ZERO, capacity))
     }
 
-    #[test]
-    fn different_sizes_work() {
-        let weak = WeakCounter::new();
-        assert_eq!(weak.count(), 0);
+    /// Create a WeakCounter whose count lives in `count` instead of in an
+    /// allocation owned by the counter, for process-wide tracking through a
+    /// `static`.
+    ///
+    /// `WeakCounter::new` can't be `const` since it allocates the shared state
+    /// used for waking waiters, and this still allocates that state once per
+    /// call. Handles created by separate calls all update `count`, but only
+    /// wake waiters created through the same call, so create a single
+    /// WeakCounter (e.g. in a `OnceLock`) and clone it rather than calling
+    /// this repeatedly.
+    ///
+    /// ```rust
+    /// use raii_counter_futures::WeakCounter;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    ///
+    /// static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// let weak = WeakCounter::from_static(&IN_FLIGHT);
+    /// let _counter = weak.spawn_upgrade();
+    /// assert_eq!(IN_FLIGHT.load(Ordering::SeqCst), 1);
+    /// ```
+    pub fn from_static(count: &'static T::Atomic) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(internal::Counter::from_static(count))
+    }
 
-        let counter = weak.spawn_upgrade_with_size(5);
-        assert_eq!(weak.count(), 5);
+    pub(crate) fn from_counter(counter: internal::Counter<T>) -> GenericWeakCounter<T> {
+        counter.add_weak();
+        GenericWeakCounter { counter }
+    }
 
-        {
-            let _counter1 = counter.clone();
-            assert_eq!(weak.count(), 10);
-            let _counter2 = weak.spawn_upgrade();
-            assert_eq!(weak.count(), 11);
-        }
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn count(&self) -> T {
+        self.counter.get()
+    }
 
-        assert_eq!(weak.count(), 5);
+    /// Like `count`, but loads with `Ordering::Relaxed`, so the value may be
+    /// even staler and says nothing about what the handles did before
+    /// changing it. Meant for cheap, high frequency sampling in metrics.
+    #[inline]
+    pub fn count_relaxed(&self) -> T {
+        self.counter.get_relaxed()
     }
 
-    #[tokio::test]
-    async fn test_wait_for_empty_works() {
-        let start = Instant::now();
-        let weak = WeakCounter::new();
+    /// A pointer to the atomic the count is stored in, e.g. for non-Rust code
+    /// to poll the count through FFI.
+    ///
+    /// The pointer stays valid for as long as any Counter or WeakCounter of
+    /// this count is alive, and must only be used for loads: changing the
+    /// count through it desyncs the count from the handles. A closeable
+    /// counter keeps its closed flag in the most significant bit of the raw
+    /// value.
+    ///
+    /// ```rust
+    /// use raii_counter_futures::WeakCounter;
+    /// use std::sync::atomic::Ordering;
+    ///
+    /// let weak = WeakCounter::new();
+    /// let _counter = weak.spawn_upgrade();
+    /// let raw = weak.as_raw_atomic();
+    /// // Safe since weak is still alive.
+    /// assert_eq!(unsafe { (*raw).load(Ordering::Acquire) }, 1);
+    /// ```
+    #[inline]
+    pub fn as_raw_atomic(&self) -> *const T::Atomic {
+        self.counter.as_ptr()
+    }
 
-        let counter1 = weak.spawn_upgrade();
-        let counter2 = counter1.clone();
-        let counter3 = counter2.clone();
-        tokio::spawn(async move {
-            delay_for(Duration::from_millis(250)).await;
-            drop(counter1);
+    /// Consume self, returning the `Arc` the count is stored in, for handing
+    /// the count to code that expects a shared atomic. Only counts created
+    /// with `GenericCounter::from_arc` keep theirs in an `Arc` of its own, so
+    /// this returns `None` for every other count, see `as_raw_atomic`.
+    ///
+    /// **Changing the count through the atomic bypasses the counter
+    /// entirely**: waiters aren't woken, `on_empty` doesn't run, and the
+    /// count is desynced from the live Counters like with `reset_to`. A
+    /// closeable counter keeps its closed flag in the most significant bit.
+    pub fn into_inner_arc(self) -> Option<Arc<T::Atomic>> {
+        self.counter.shared_arc()
+    }
 
-            delay_for(Duration::from_millis(500)).await;
-            drop(counter2);
+    /// Whether the count is currently zero.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count() == T::ZERO
+    }
 
-            delay_for(Duration::from_millis(100)).await;
-            drop(counter3);
-        });
+    /// Whether this closeable counter has closed, by draining or through
+    /// `drain_and_close`, after which it stays closed and upgrades fail.
+    /// Always `false` for counters not created with `new_closeable`.
+    #[inline]
+    pub fn has_closed(&self) -> bool {
+        self.counter.is_closed()
+    }
 
-        weak.wait_for_empty().await;
-        let elapsed = start.elapsed();
+    /// The number of WeakCounters observing this count, including self.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn weak_count(&self) -> usize {
+        self.counter.weak_count()
+    }
 
-        assert!(elapsed >= Duration::from_millis(850));
-        assert!(elapsed < Duration::from_millis(900));
+    /// Whether self is the only WeakCounter observing this count, e.g. so a
+    /// background task watching the count can exit once nobody else is. Live
+    /// Counters don't count as observers.
+    ///
+    /// This method is inherently racey. Another observer may be created from a
+    /// Counter right after this returns `true`.
+    #[inline]
+    pub fn is_last_observer(&self) -> bool {
+        self.weak_count() == 1
+    }
+
+    /// The lifetime totals `(added, subtracted)` of the count, where the
+    /// initial count counts as added. The current count is always their
+    /// difference, so comparing them with what was expected helps track down
+    /// leaked clones or double drops.
+    ///
+    /// Only available with the `diagnostics` feature. The totals are loaded
+    /// with `Ordering::Relaxed` and may lag the count slightly.
+    #[cfg(feature = "diagnostics")]
+    #[inline]
+    pub fn lifetime_stats(&self) -> (u64, u64) {
+        self.counter.lifetime_stats()
+    }
+
+    /// The last values the count changed to, oldest first, starting with its
+    /// initial value. Up to 32 are kept, older ones are overwritten.
+    ///
+    /// Only available with the `history` feature. Samples are recorded right
+    /// after each change without a lock, so concurrent changes may be
+    /// recorded out of order, and a sample being written while this runs may
+    /// be missing or stale.
+    #[cfg(feature = "history")]
+    pub fn recent_counts(&self) -> Vec<T> {
+        self.counter.recent_counts()
+    }
+
+    /// Like `count`, but returns `Err` if more has been subtracted from the
+    /// count over its lifetime than was ever added, which is impossible for a
+    /// counter that is only changed through its handles. Catches e.g. a
+    /// `release` of more than was held, or a `reset_to` above the live
+    /// Counters once they have dropped.
+    ///
+    /// Only available with the `diagnostics` feature. A desync that hasn't
+    /// subtracted more than was added yet, like a `reset_to` below the live
+    /// Counters, isn't detected until they drop.
+    #[cfg(feature = "diagnostics")]
+    pub fn count_checked(&self) -> Result<T, Desync> {
+        self.counter.count_checked()
+    }
+
+    /// The highest count observed since the counter was created or since the
+    /// last call to `reset_peak`.
+    ///
+    /// This method is inherently racey. Assume the count will have changed once
+    /// the value is observed.
+    #[inline]
+    pub fn peak(&self) -> T {
+        self.counter.peak()
+    }
+
+    /// Restart peak tracking from the current count
+    #[inline]
+    pub fn reset_peak(&self) {
+        self.counter.reset_peak();
+    }
+
+    /// Whether `other` observes the same underlying count as self. This
+    /// compares identity, not the current count.
+    #[inline]
+    pub fn same_counter(&self, other: &GenericWeakCounter<T>) -> bool {
+        self.counter.ptr_eq(&other.counter)
+    }
+
+    /// Whether `other` contributes to the same underlying count as self
+    #[inline]
+    pub fn same_counter_as(&self, other: &GenericCounter<T>) -> bool {
+        self.counter.ptr_eq(&other.counter)
+    }
+
+    /// The name this counter was created with, if any
+    #[inline]
+    pub fn name(&self) -> Option<&'static str> {
+        self.counter.name()
+    }
+
+    /// The capacity this counter was created with, if any
+    #[inline]
+    pub fn capacity(&self) -> Option<T> {
+        self.counter.capacity()
+    }
+
+    /// The fraction of the capacity currently in use, from `0.0` for an empty
+    /// counter to `1.0` for a full one, or `None` if the counter wasn't
+    /// created with a capacity. Just as racey as `count`.
+    pub fn saturation_ratio(&self) -> Option<f64> {
+        let capacity = self.capacity()?;
+        if capacity == T::ZERO {
+            return Some(1.0);
+        }
+        Some(self.count().to_u64() as f64 / capacity.to_u64() as f64)
+    }
+
+    /// Returns a closure that applies `f` to the current count each time it
+    /// is called, for handing a derived view of the count (e.g. whether it
+    /// is saturated) to code that shouldn't get at the counter itself. The
+    /// closure holds on to its own WeakCounter, so it can outlive self.
+    ///
+    /// ```rust
+    /// use raii_counter_futures::WeakCounter;
+    ///
+    /// let weak = WeakCounter::new();
+    /// let busy = weak.map_count(|count| count > 0);
+    /// assert!(!busy());
+    /// let _counter = weak.spawn_upgrade();
+    /// assert!(busy());
+    /// ```
+    pub fn map_count<U, F: Fn(T) -> U>(&self, f: F) -> impl Fn() -> U {
+        let weak = self.clone();
+        move || f(weak.count())
+    }
+
+    /// Forcibly overwrite the count with `value`.
+    ///
+    /// **This desyncs the count from the live Counters.** They still subtract
+    /// their size when dropped, so resetting below the sum of their sizes
+    /// makes the count underflow later, which panics in debug builds and
+    /// wraps in release builds unless the counter is saturating. Only use this
+    /// in tests, or to recover once every Counter is known to be gone.
+    ///
+    /// Waiters are woken as if the count had changed normally: resetting a
+    /// nonzero count to zero counts as a drain, resolving `wait_for_empty` and
+    /// running `on_empty`. A closeable counter is reopened. With the
+    /// `diagnostics` feature, `lifetime_stats` no longer adds up afterwards.
+    pub fn reset_to(&self, value: T) {
+        self.counter.reset_to(value);
+    }
+
+    /// Like `reset_to`, but only overwrites the count with `new` if it is
+    /// exactly `expected`, in a single compare-and-swap, returning `Err` with
+    /// the actual count otherwise. Avoids racing a check of `count` against the
+    /// reset, e.g. for a test asserting the count and draining it in one shot.
+    ///
+    /// **This desyncs the count from the live Counters** just like
+    /// `reset_to` does, with the same caveats. Waiters and `on_empty` are only
+    /// affected if the swap happens.
+    pub fn compare_and_reset(&self, expected: T, new: T) -> Result<(), T> {
+        self.counter.compare_and_reset(expected, new)
+    }
+
+    /// Subtract `n` from the count without dropping a Counter, clamping at
+    /// zero, for holds whose release is signalled by something other than a
+    /// Drop (e.g. an FFI callback). The manual counterpart to leaking an
+    /// upgraded Counter.
+    ///
+    /// Releasing more than was added on the side desyncs the count from the
+    /// live Counters, draining it early and making it underflow once they
+    /// drop unless the counter is saturating. Draining the count through
+    /// `release` wakes waiters and runs `on_empty` like dropping the last
+    /// Counter does.
+    pub fn release(&self, n: T) {
+        self.counter.release(n);
+    }
+
+    /// Consumes self, becomes a Counter
+    ///
+    /// **Panics if the counter is closed or full**, which only counters
+    /// created with `new_closeable` or a capacity can be. Use `try_upgrade`
+    /// for those.
+    ///
+    /// **Otherwise upgrading succeeds even once the count has drained to
+    /// zero.** An upgrade after the last Counter dropped revives the count
+    /// from 0 to 1, so a task already woken by `wait_for_empty` won't see the
+    /// new hold. If a drained counter should stay drained, use
+    /// `new_closeable` with `try_upgrade` and close it before waiting. With
+    /// the `tracing` feature, debug builds log a warning whenever an upgrade
+    /// revives a drained count.
+    pub fn upgrade(self) -> GenericCounter<T> {
+        self.spawn_upgrade()
+    }
+
+    /// Instead of clone + upgrade, this will only clone once
+    /// Defaults to a Counter of size 1
+    ///
+    /// Like `upgrade`, this panics if the counter is closed or full, and
+    /// otherwise revives a count that has drained to zero.
+    pub fn spawn_upgrade(&self) -> GenericCounter<T> {
+        self.spawn_upgrade_with_size(T::ONE)
+    }
+
+    /// Instead of clone + upgrade, this will only clone once
+    ///
+    /// Panics if the counter is closed or full, see
+    /// `try_spawn_upgrade_with_size`.
+    pub fn spawn_upgrade_with_size(&self, size: T) -> GenericCounter<T> {
+        self.try_spawn_upgrade_with_size(size)
+            .expect("upgraded a closed or full counter, use try_spawn_upgrade instead")
+    }
+
+    /// Like `spawn_upgrade_with_size`, but returns `Err` with the count at the
+    /// time of the attempt if adding `size` would overflow `T`, or if the
+    /// counter is closed or full
+    pub fn spawn_upgrade_checked(&self, size: T) -> Result<GenericCounter<T>, T> {
+        let previous = self.counter.try_fetch_add(size)?;
+        self.counter.upgraded(previous, size);
+        Ok(GenericCounter {
+            counter: self.counter.clone(),
+            size,
+        })
+    }
+
+    /// Spawn `n` Counters of size 1, adding to the count once for the whole
+    /// batch rather than once per Counter
+    ///
+    /// Panics if the counter is closed or full, see `try_spawn_upgrade_many`.
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn spawn_upgrade_many(&self, n: usize) -> Vec<GenericCounter<T>> {
+        self.spawn_upgrade_many_with_size(n, T::ONE)
+    }
+
+    /// Like `spawn_upgrade_many`, but each Counter has the given `size`
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn spawn_upgrade_many_with_size(&self, n: usize, size: T) -> Vec<GenericCounter<T>> {
+        self.try_spawn_upgrade_many_with_size(n, size)
+            .expect("upgraded a closed or full counter, use try_spawn_upgrade_many instead")
+    }
+
+    /// Consumes self, becomes a Counter unless the counter is closed or full
+    #[must_use = "dropping the Counter immediately releases the count; bind it to a variable"]
+    pub fn try_upgrade(self) -> Option<GenericCounter<T>> {
+        self.try_spawn_upgrade()
+    }
+
+    /// Like `spawn_upgrade`, but returns `None` if the counter is closed or
+    /// full
+    #[must_use = "dropping the Counter immediately releases the count; bind it to a variable"]
+    pub fn try_spawn_upgrade(&self) -> Option<GenericCounter<T>> {
+        self.try_spawn_upgrade_with_size(T::ONE)
+    }
+
+    /// Like `spawn_upgrade_with_size`, but returns `None` if the counter is
+    /// closed or if `size` doesn't fit in the remaining capacity
+    ///
+    /// A counter that isn't closeable or bounded is never refused, so this
+    /// still revives a count that has drained to zero, see `upgrade`.
+    #[must_use = "dropping the Counter immediately releases the count; bind it to a variable"]
+    pub fn try_spawn_upgrade_with_size(&self, size: T) -> Option<GenericCounter<T>> {
+        let previous = if self.counter.restricts_upgrades() {
+            self.counter.try_fetch_add(size).ok()?
+        } else {
+            self.counter.fetch_add(size)
+        };
+        self.counter.upgraded(previous, size);
+        Some(GenericCounter {
+            counter: self.counter.clone(),
+            size,
+        })
+    }
+
+    /// Like `spawn_upgrade_many`, but returns `None` if the counter is closed
+    /// or the whole batch doesn't fit under the capacity. Either every Counter
+    /// is spawned or none are.
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn try_spawn_upgrade_many(&self, n: usize) -> Option<Vec<GenericCounter<T>>> {
+        self.try_spawn_upgrade_many_with_size(n, T::ONE)
+    }
+
+    /// Like `try_spawn_upgrade_many`, but each Counter has the given `size`.
+    /// Also returns `None` if the batch's total size overflows `T`.
+    #[must_use = "dropping the Counters immediately releases the count; bind them to a variable"]
+    pub fn try_spawn_upgrade_many_with_size(
+        &self,
+        n: usize,
+        size: T,
+    ) -> Option<Vec<GenericCounter<T>>> {
+        let amount = size.checked_mul_usize(n)?;
+        let previous = if self.counter.restricts_upgrades() {
+            self.counter.try_fetch_add(amount).ok()?
+        } else {
+            self.counter.fetch_add(amount)
+        };
+        self.counter.upgraded(previous, amount);
+        Some(
+            (0..n)
+                .map(|_| GenericCounter {
+                    counter: self.counter.clone(),
+                    size,
+                })
+                .collect(),
+        )
+    }
+
+    /// Run `f` while holding a Counter spawned from self, dropping it once
+    /// `f` returns or unwinds. Panics like `spawn_upgrade` if the counter is
+    /// closed or full.
+    pub fn scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _counter = self.spawn_upgrade();
+        f()
+    }
+
+    /// Like `scope`, but holds the Counter until `f` completes or is dropped.
+    /// Panics like `spawn_upgrade` if the counter is closed or full, which
+    /// happens when the returned future is first polled.
+    pub async fn scope_async<F, R>(&self, f: F) -> R
+    where
+        F: Future<Output = R>,
+    {
+        let _counter = self.spawn_upgrade();
+        f.await
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: CountInt> GenericWeakCounter<T> {
+    /// Returns a future that waits until the counter contains a 0 value
+    ///
+    /// The future is cancellation safe: dropping it before it resolves leaves
+    /// other waiters unaffected, and every waiter registered before a drain
+    /// is woken by it. A waiter only resolves once it sees the zero count
+    /// though, so if the count is raised again before the waiter gets to run
+    /// it keeps waiting for the next drain.
+    pub async fn wait_for_empty(&self) {
+        self.counter.wait_for_empty().await;
+    }
+
+    /// Like `wait_for_empty`, but resolves to how long it waited, measured
+    /// with the monotonic `Instant` clock from when the future is first
+    /// polled. Resolves to a near-zero `Duration` if the counter is already
+    /// empty.
+    pub async fn wait_for_empty_timed(&self) -> Duration {
+        let start = Instant::now();
+        self.counter.wait_for_empty().await;
+        start.elapsed()
+    }
+
+    /// Like `wait_for_empty`, but instead of waking every waiter at once, a
+    /// drain wakes the one that has been waiting the longest, which wakes the
+    /// next once it has seen the zero count. This avoids a thundering herd of
+    /// waiters re-checking a count that bounces off zero, at the cost of
+    /// waking them in turn when it doesn't.
+    ///
+    /// A woken waiter that finds the count raised again goes to the back of
+    /// the line. Waiters must be polled to pass the wakeup on, so one that is
+    /// woken but never polled again (without being dropped) holds up the
+    /// waiters behind it.
+    ///
+    /// This is not faster: in the `thundering_herd` benchmark both waits take
+    /// about as long to resolve. Use it when waiters should finish in the
+    /// order they started waiting, or when waking one costs enough that a
+    /// single wakeup per drain matters.
+    pub async fn wait_for_empty_fair(&self) {
+        self.counter.wait_for_empty_fair().await;
+    }
+
+    /// Polls once for what `wait_for_empty` waits on, returning whether the
+    /// counter is empty right now without waiting.
+    ///
+    /// Waiters only ever resolve by checking the count itself, the events
+    /// just wake them up to check it again, so this always agrees with
+    /// `is_empty`. It is just as racey.
+    #[inline]
+    pub fn try_wait_for_empty(&self) -> bool {
+        self.counter.get() == T::ZERO
+    }
+
+    /// Like `wait_for_empty`, but returns a named future that can be stored
+    /// without boxing. The future is `Send`, `Unpin`, and holds on to the
+    /// count itself, so it can outlive self.
+    pub fn empty_future(&self) -> EmptyFuture<T> {
+        EmptyFuture::new(self.counter.clone())
+    }
+
+    /// Blocks the current thread until the counter contains a 0 value
+    pub fn wait_for_empty_blocking(&self) {
+        self.counter.wait_for_empty_blocking();
+    }
+
+    /// Close this closeable counter right away, so every upgrade from now on
+    /// fails, then wait for the Counters still alive to drain. Closing before
+    /// awaiting leaves no window for an upgrade to slip in once shutdown has
+    /// begun.
+    ///
+    /// The counter is closed when the future is first polled. Counters not
+    /// created with `new_closeable` can't be closed, so for them this only
+    /// waits for the drain.
+    pub async fn drain_and_close(&self) {
+        self.counter.close();
+        self.counter.wait_for_empty().await;
+    }
+
+    /// Returns a future that waits until the count differs from `since` and
+    /// resolves to the new count, whichever direction it moved in. Resolves
+    /// immediately if it already differs.
+    ///
+    /// A count that changes and changes back before the waiter gets to run
+    /// goes unnoticed, so pass the value returned by the previous call as
+    /// `since` to follow every update that is observed.
+    pub async fn wait_for_change(&self, since: T) -> T {
+        self.counter.wait_for_change(since).await
+    }
+
+    /// Returns a future that waits until either the counter contains a 0
+    /// value, resolving to `Either::Left(())`, or `other` completes, resolving
+    /// to `Either::Right` with its output. Whichever doesn't resolve first is
+    /// dropped, e.g. for bailing out of a graceful shutdown on a hard shutdown
+    /// signal.
+    ///
+    /// The drain is checked first, so it wins if both are ready at once.
+    pub async fn wait_for_empty_or<F: Future>(&self, other: F) -> Either<(), F::Output> {
+        let mut empty = pin!(self.wait_for_empty());
+        let mut other = pin!(other);
+        poll_fn(|cx| {
+            if empty.as_mut().poll(cx).is_ready() {
+                Poll::Ready(Either::Left(()))
+            } else if let Poll::Ready(output) = other.as_mut().poll(cx) {
+                Poll::Ready(Either::Right(output))
+            } else {
+                Poll::Pending
+            }
+        })
+        .await
+    }
+
+    /// Returns a future that waits until the counter contains a 0 value or
+    /// `dur` has elapsed, whichever comes first. Resolves to `true` if the
+    /// counter drained in time and `false` on timeout.
+    pub async fn wait_for_empty_timeout(&self, dur: Duration) -> bool {
+        self.counter.wait_for_empty_timeout(dur).await
+    }
+
+    /// Returns a future that waits until the counter contains a value less
+    /// than or equal to `target`
+    pub async fn wait_for_count(&self, target: T) {
+        self.counter.wait_for_count(target).await;
+    }
+
+    /// Returns a future that waits until the counter contains a value greater
+    /// than or equal to `n`
+    pub async fn wait_for_at_least(&self, n: T) {
+        self.counter.wait_for_at_least(n).await;
+    }
+
+    /// Returns a future that waits until there is room for `needed` under the
+    /// capacity, without taking it, e.g. to wake a dispatcher once it has
+    /// something to dispatch to. Resolves to `true` once there is room, or to
+    /// `false` right away if `needed` is more than the capacity, which no
+    /// amount of waiting would satisfy. Counters without a capacity are only
+    /// bounded by `T`.
+    ///
+    /// The room can be taken by someone else before an upgrade is attempted,
+    /// use `acquire_with_size` to wait and take it at once. Whether the
+    /// counter is closed isn't considered.
+    pub async fn wait_for_capacity(&self, needed: T) -> bool {
+        self.counter.wait_for_room(needed).await
+    }
+
+    /// Returns a future that waits until there is room under the capacity,
+    /// then upgrades to a Counter of size 1
+    ///
+    /// The future never resolves if the counter is closed, and dropping it
+    /// before it resolves leaves the count untouched.
+    ///
+    /// No fairness is given: waiters aren't queued, every decrease wakes all
+    /// of them and whichever retries first takes the room. A waiter can be
+    /// overtaken by later ones, or by `try_spawn_upgrade`, for as long as
+    /// others keep taking the room first.
+    pub async fn acquire(&self) -> GenericCounter<T> {
+        self.acquire_with_size(T::ONE).await
+    }
+
+    /// Returns a future that waits until there is room for `size` under the
+    /// capacity, then upgrades to a Counter of that size
+    ///
+    /// Like `acquire`, waiters take the room in no particular order.
+    pub async fn acquire_with_size(&self, size: T) -> GenericCounter<T> {
+        let previous = self.counter.acquire(size).await;
+        self.counter.upgraded(previous, size);
+        GenericCounter {
+            counter: self.counter.clone(),
+            size,
+        }
+    }
+
+    /// Returns a future that waits until the count is strictly below
+    /// `threshold`, then upgrades to a Counter of `size`
+    ///
+    /// The check and the increment happen in a single compare-and-swap, so the
+    /// count is below `threshold` at the moment `size` is added. The capacity,
+    /// if any, still applies.
+    /// Dropping the future before it resolves leaves the count untouched.
+    pub async fn acquire_when_below(&self, threshold: T, size: T) -> GenericCounter<T> {
+        let previous = self.counter.acquire_below(threshold, size).await;
+        self.counter.upgraded(previous, size);
+        GenericCounter {
+            counter: self.counter.clone(),
+            size,
+        }
+    }
+
+    /// Returns a stream of the count, yielding the current value on first poll
+    /// and the new value each time it changes afterwards.
+    ///
+    /// Rapid updates are coalesced: only the latest value is yielded once the
+    /// stream is polled again, so intermediate values may be skipped, but the
+    /// stream always catches up to the most recent count. The stream never
+    /// terminates.
+    pub fn count_stream(&self) -> impl Stream<Item = T> + Unpin {
+        stream::CountStream::new(self.counter.clone())
+    }
+
+    /// Returns an Observer that awaits changes to the count on demand through
+    /// `Observer::changed`, which fits `select!` loops better than
+    /// `count_stream`. The Observer starts out having seen the current count.
+    pub fn observer(&self) -> Observer<T> {
+        Observer::new(self.clone())
+    }
+
+    /// Returns a stream that yields once for every time the count
+    /// transitions to zero after the stream was created.
+    ///
+    /// Unlike `count_stream`, only the drain edge is signaled, and drains are
+    /// never coalesced: a count that drains three times before the stream is
+    /// polled yields three items. A count that stays at zero yields nothing.
+    /// The stream never terminates.
+    pub fn empty_events(&self) -> impl Stream<Item = ()> + Unpin {
+        stream::EmptyEvents::new(self.counter.clone())
+    }
+}
+
+impl<T: CountInt> Clone for GenericWeakCounter<T> {
+    fn clone(&self) -> Self {
+        GenericWeakCounter::from_counter(self.counter.clone())
+    }
+}
+
+/// WeakCounters are equal when they observe the same underlying count, like
+/// `same_counter`. Two distinct counters are never equal, even at the same
+/// count.
+impl<T: CountInt> PartialEq for GenericWeakCounter<T> {
+    fn eq(&self, other: &GenericWeakCounter<T>) -> bool {
+        self.same_counter(other)
+    }
+}
+
+impl<T: CountInt> Eq for GenericWeakCounter<T> {}
+
+/// Hashes the identity of the underlying count rather than its value, which
+/// keeps the hash stable while the count changes. Clippy's
+/// `mutable_key_type` lint flags WeakCounter keys regardless and can safely
+/// be allowed.
+impl<T: CountInt> Hash for GenericWeakCounter<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.counter.addr().hash(state);
+    }
+}
+
+/// Orders WeakCounters by the address of the underlying count, consistent
+/// with `Eq`, e.g. for `BTreeMap` keys. This is an identity ordering, not a
+/// count ordering: it is arbitrary, but stable for as long as the count is
+/// alive.
+impl<T: CountInt> Ord for GenericWeakCounter<T> {
+    fn cmp(&self, other: &GenericWeakCounter<T>) -> Ordering {
+        self.counter.addr().cmp(&other.counter.addr())
+    }
+}
+
+impl<T: CountInt> PartialOrd for GenericWeakCounter<T> {
+    fn partial_cmp(&self, other: &GenericWeakCounter<T>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: CountInt> Default for GenericWeakCounter<T> {
+    fn default() -> Self {
+        GenericWeakCounter::new()
+    }
+}
+
+impl<T: CountInt> Debug for GenericWeakCounter<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("WeakCounter")
+            .field("count", &self.count())
+            .field("capacity", &self.capacity())
+            .field("name", &self.name())
+            .finish()
+    }
+}
+
+impl<T: CountInt> Display for GenericWeakCounter<T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let count = DisplayCount(&self.counter);
+        match self.name() {
+            Some(name) => write!(f, "WeakCounter(name={}, count={})", name, count),
+            None => write!(f, "WeakCounter(count={})", count),
+        }
+    }
+}
+
+impl<T: CountInt> Drop for GenericWeakCounter<T> {
+    fn drop(&mut self) {
+        self.counter.sub_weak();
+    }
+}
+
+#[cfg(all(test, not(loom)))]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    #[cfg(feature = "std")]
+    use futures::StreamExt;
+    use std::string::ToString;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    #[cfg(feature = "std")]
+    use std::time::Instant;
+    use std::vec::Vec;
+    use std::{format, vec};
+    use tokio::time::sleep;
+
+    static_assertions::assert_impl_all!(Counter: Send, Sync);
+    static_assertions::assert_impl_all!(WeakCounter: Send, Sync);
+    #[cfg(target_has_atomic = "32")]
+    static_assertions::assert_impl_all!(Counter32: Send, Sync);
+    #[cfg(target_has_atomic = "64")]
+    static_assertions::assert_impl_all!(Counter64: Send, Sync);
+    static_assertions::assert_impl_all!(CounterBuilder: Send, Sync);
+    #[cfg(feature = "std")]
+    static_assertions::assert_impl_all!(CountdownLatch: Send, Sync);
+    #[cfg(feature = "std")]
+    static_assertions::assert_impl_all!(WaitGroup: Send, Sync);
+    #[cfg(feature = "std")]
+    static_assertions::assert_impl_all!(WaitGroupGuard: Send, Sync);
+    static_assertions::assert_impl_all!(Budget: Send, Sync);
+    static_assertions::assert_impl_all!(BudgetGuard: Send, Sync);
+    #[cfg(feature = "std")]
+    static_assertions::assert_impl_all!(EmptyFuture: Send);
+    #[cfg(feature = "std")]
+    static_assertions::assert_impl_all!(CounterPool: Send, Sync);
+    static_assertions::assert_impl_all!(internal::OnEmpty: Send, Sync);
+
+    #[test]
+    fn it_works() {
+        let counter = Counter::new();
+        assert_eq!(counter.count(), 1);
+
+        let weak = counter.downgrade();
+        assert_eq!(weak.count(), 0);
+
+        {
+            let _counter1 = weak.spawn_upgrade();
+            assert_eq!(weak.count(), 1);
+            let _counter2 = weak.spawn_upgrade();
+            assert_eq!(weak.count(), 2);
+        }
+
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn new_paired_shares_count() {
+        let (counter, weak) = Counter::new_paired();
+        assert!(weak.same_counter_as(&counter));
+        assert_eq!(weak.count(), 1);
+
+        let clone = counter.clone();
+        assert_eq!(weak.count(), 2);
+        drop((counter, clone));
+        assert!(weak.is_empty());
+    }
+
+    #[test]
+    fn downgrade_ref_works() {
+        let counter = Counter::new();
+        let weak = counter.downgrade_ref();
+        assert_eq!(weak.count(), 1);
+
+        let clone = counter.clone();
+        assert_eq!(weak.count(), 2);
+
+        drop(clone);
+        drop(counter);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn weak_count_works() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.weak_count(), 1);
+
+        let counter = weak.spawn_upgrade();
+        assert_eq!(counter.weak_count(), 1);
+
+        {
+            let _weak1 = weak.clone();
+            let _weak2 = counter.downgrade_ref();
+            assert_eq!(weak.weak_count(), 3);
+        }
+        assert_eq!(weak.weak_count(), 1);
+
+        drop(weak);
+        assert_eq!(counter.weak_count(), 0);
+
+        let weak = counter.downgrade();
+        assert_eq!(weak.weak_count(), 1);
+    }
+
+    #[test]
+    fn is_last_observer_works() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        assert!(weak.is_last_observer());
+
+        let observer = counter.downgrade_ref();
+        assert!(!weak.is_last_observer());
+        assert!(!observer.is_last_observer());
+
+        drop(weak);
+        assert!(observer.is_last_observer());
+    }
+
+    #[test]
+    fn closeable_cannot_be_revived() {
+        let weak = WeakCounter::new_closeable();
+
+        let counter = weak.try_spawn_upgrade().unwrap();
+        let clone = counter.clone();
+        assert_eq!(weak.count(), 2);
+
+        drop(counter);
+        assert!(weak.try_spawn_upgrade_with_size(2).is_some());
+        assert_eq!(weak.count(), 1);
+
+        drop(clone);
+        assert_eq!(weak.count(), 0);
+        assert!(weak.try_spawn_upgrade().is_none());
+        assert!(weak.clone().try_upgrade().is_none());
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn has_closed_stays_true_after_drain() {
+        let weak = WeakCounter::new_closeable();
+        assert!(!weak.has_closed());
+
+        let counter = weak.spawn_upgrade();
+        drop(counter.clone());
+        assert!(!weak.has_closed());
+
+        drop(counter);
+        assert!(weak.has_closed());
+        assert!(weak.try_spawn_upgrade().is_none());
+        assert!(weak.has_closed());
+
+        let weak = WeakCounter::new();
+        drop(weak.spawn_upgrade());
+        assert!(!weak.has_closed());
+    }
+
+    #[test]
+    #[should_panic(expected = "upgraded a closed or full counter")]
+    fn closeable_spawn_upgrade_panics_once_closed() {
+        let weak = WeakCounter::new_closeable();
+        drop(weak.spawn_upgrade());
+        let _counter = weak.spawn_upgrade();
+    }
+
+    #[test]
+    fn capacity_limits_upgrades() {
+        let weak = WeakCounter::new_with_capacity(4);
+        assert_eq!(weak.capacity(), Some(4));
+        assert_eq!(WeakCounter::new().capacity(), None);
+
+        let counter1 = weak.try_spawn_upgrade_with_size(3).unwrap();
+        assert!(weak.try_spawn_upgrade_with_size(2).is_none());
+        let counter2 = weak.try_spawn_upgrade().unwrap();
+        assert!(weak.try_spawn_upgrade().is_none());
+        assert_eq!(weak.count(), 4);
+
+        drop(counter1);
+        assert!(weak.try_spawn_upgrade_with_size(5).is_none());
+        assert!(weak.try_spawn_upgrade_with_size(3).is_some());
+        drop(counter2);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn capacity_holds_under_contention() {
+        let weak = WeakCounter::new_with_capacity(4);
+        let held = Arc::new(AtomicUsize::new(0));
+        let admitted = Arc::new(AtomicUsize::new(0));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let weak = weak.clone();
+                let held = Arc::clone(&held);
+                let admitted = Arc::clone(&admitted);
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        if let Some(counter) = weak.try_spawn_upgrade() {
+                            let now_held = held.fetch_add(1, Ordering::SeqCst) + 1;
+                            assert!(now_held <= 4);
+                            assert!(weak.count() <= 4);
+                            admitted.fetch_add(1, Ordering::SeqCst);
+                            held.fetch_sub(1, Ordering::SeqCst);
+                            drop(counter);
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert!(admitted.load(Ordering::SeqCst) > 0);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    // The count is interior mutable, but it never feeds into the hash.
+    #[allow(clippy::mutable_key_type)]
+    fn hash_and_eq_use_identity() {
+        use std::collections::HashSet;
+
+        let weak = WeakCounter::new();
+        let other = WeakCounter::new();
+        assert_eq!(weak, weak.clone());
+        assert_ne!(weak, other);
+
+        let mut set = HashSet::new();
+        set.insert(weak.clone());
+        let _counter = weak.spawn_upgrade();
+        set.insert(weak.clone());
+        set.insert(weak);
+        assert_eq!(set.len(), 1);
+
+        set.insert(other);
+        set.insert(WeakCounter::new());
+        assert_eq!(set.len(), 3);
+    }
+
+    #[test]
+    #[allow(clippy::mutable_key_type)]
+    fn ord_uses_identity() {
+        use std::collections::BTreeMap;
+
+        let weaks: Vec<WeakCounter> = (0..4).map(|_| WeakCounter::new()).collect();
+        let mut names = BTreeMap::new();
+        for (i, weak) in weaks.iter().enumerate() {
+            names.insert(weak.clone(), i);
+        }
+        let _counter = weaks[2].spawn_upgrade();
+        assert_eq!(names.len(), 4);
+        for (i, weak) in weaks.iter().enumerate() {
+            assert_eq!(names.get(&weak.clone()), Some(&i));
+        }
+        assert_eq!(names.get(&WeakCounter::new()), None);
+    }
+
+    #[test]
+    fn same_counter_compares_identity() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let other = WeakCounter::new();
+        let other_counter = other.spawn_upgrade();
+
+        assert!(weak.same_counter(&weak.clone()));
+        assert!(weak.same_counter(&counter.downgrade_ref()));
+        assert!(weak.same_counter_as(&counter));
+        assert!(counter.same_counter(&counter.clone()));
+        assert!(counter.same_counter_as(&weak));
+
+        assert!(!weak.same_counter(&other));
+        assert!(!weak.same_counter_as(&other_counter));
+        assert!(!counter.same_counter(&other_counter));
+        assert!(!counter.same_counter_as(&other));
+    }
+
+    #[test]
+    fn peak_tracks_maximum() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.peak(), 0);
+
+        let counters: Vec<_> = (0..5).map(|_| weak.spawn_upgrade()).collect();
+        let big = weak.spawn_upgrade_with_size(3);
+        assert_eq!(weak.peak(), 8);
+
+        drop(counters);
+        assert_eq!(weak.count(), 3);
+        assert_eq!(big.peak(), 8);
+
+        let _clone = big.clone();
+        assert_eq!(weak.peak(), 8);
+
+        weak.reset_peak();
+        assert_eq!(weak.peak(), 6);
+        drop(big);
+        let _counter = weak.spawn_upgrade();
+        assert_eq!(weak.peak(), 6);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn from_static_works_across_threads() {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let weak = WeakCounter::from_static(&COUNT);
+
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let weak = weak.clone();
+                thread::spawn(move || {
+                    for _ in 0..100 {
+                        let _counter = weak.spawn_upgrade();
+                        assert!(COUNT.load(Ordering::SeqCst) >= 1);
+                    }
+                })
+            })
+            .collect();
+
+        let counter = weak.spawn_upgrade_with_size(2);
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(COUNT.load(Ordering::SeqCst), 2);
+
+        drop(counter);
+        assert_eq!(weak.count(), 0);
+        weak.wait_for_empty_blocking();
+    }
+
+    #[cfg(all(feature = "tracing", feature = "std"))]
+    #[test]
+    #[tracing_test::traced_test]
+    fn tracing_emits_events() {
+        let weak = WeakCounter::new_named("db_pool");
+        let counter = weak.spawn_upgrade_with_size(3);
+        assert_eq!(counter.name(), Some("db_pool"));
+        assert!(logs_contain("name=\"db_pool\" delta=3 count=3"));
+        assert!(logs_contain("counter incremented"));
+
+        drop(counter);
+        assert!(logs_contain("delta=3 count=0"));
+        assert!(logs_contain("counter decremented"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pool_recycles_handles() {
+        let weak = WeakCounter::new();
+        let pool = CounterPool::new(weak.clone());
+        let first = pool.acquire();
+        let second = pool.acquire();
+        assert_eq!(second.count(), 2);
+        assert_eq!(pool.idle(), 0);
+
+        drop(first);
+        drop(second);
+        assert_eq!(weak.count(), 0);
+        assert_eq!(pool.idle(), 2);
+
+        let _counter = pool.acquire();
+        assert_eq!(weak.count(), 1);
+        assert_eq!(pool.idle(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_budget_exhausts_and_recovers() {
+        let budget = Budget::new(2);
+        let first = budget.try_acquire().unwrap();
+        let _second = budget.try_acquire().unwrap();
+        assert_eq!(budget.remaining(), 0);
+        assert!(budget.try_acquire().is_none());
+
+        let waiter = {
+            let budget = budget.clone();
+            tokio::spawn(async move { budget.acquire().await })
+        };
+        sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        drop(first);
+        let _third = waiter.await.unwrap();
+        assert_eq!(budget.remaining(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn compare_and_reset_only_swaps_the_expected_count() {
+        let drains = Arc::new(AtomicUsize::new(0));
+        let counter = {
+            let drains = Arc::clone(&drains);
+            Counter::new_with_on_empty(1, move || {
+                drains.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let weak = counter.downgrade_ref();
+        std::mem::forget(counter.clone());
+
+        assert_eq!(weak.compare_and_reset(1, 0), Err(2));
+        assert_eq!(weak.count(), 2);
+        assert_eq!(drains.load(Ordering::SeqCst), 0);
+
+        assert_eq!(weak.compare_and_reset(2, 0), Ok(()));
+        assert_eq!(weak.count(), 0);
+        assert!(weak.try_wait_for_empty());
+        assert_eq!(drains.load(Ordering::SeqCst), 1);
+
+        // Keep the remaining Counter from underflowing the drained count.
+        weak.reset_to(1);
+        drop(counter);
+    }
+
+    #[test]
+    fn saturation_ratio_tracks_capacity() {
+        let weak = WeakCounter::new_with_capacity(4);
+        let full = weak.map_count(|count| count == 4);
+        assert_eq!(weak.saturation_ratio(), Some(0.0));
+
+        let _counters = weak.spawn_upgrade_many(3);
+        assert_eq!(weak.saturation_ratio(), Some(0.75));
+        assert!(!full());
+
+        let _last = weak.spawn_upgrade();
+        assert_eq!(weak.saturation_ratio(), Some(1.0));
+        assert!(full());
+        assert_eq!(WeakCounter::new().saturation_ratio(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_timed_measures_the_drain() {
+        let counter = Counter::new();
+        let weak = counter.downgrade_ref();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            drop(counter);
+        });
+
+        let waited = weak.wait_for_empty_timed().await;
+        assert!(waited >= Duration::from_millis(50));
+        assert!(waited < Duration::from_secs(1));
+        assert!(weak.wait_for_empty_timed().await < Duration::from_millis(50));
+    }
+
+    #[test]
+    fn upgrade_revives_a_drained_count() {
+        let weak = WeakCounter::new();
+        drop(weak.spawn_upgrade());
+        assert_eq!(weak.count(), 0);
+
+        let counter = weak.clone().upgrade();
+        assert_eq!(counter.count(), 1);
+    }
+
+    #[cfg(all(feature = "tracing", feature = "std", debug_assertions))]
+    #[test]
+    #[tracing_test::traced_test]
+    fn tracing_warns_on_revival() {
+        let weak = WeakCounter::new();
+        drop(weak.spawn_upgrade());
+        assert!(!logs_contain("counter revived after draining to zero"));
+
+        let _counter = weak.spawn_upgrade();
+        assert!(logs_contain("counter revived after draining to zero"));
+    }
+
+    #[cfg(all(feature = "tracing", feature = "std", debug_assertions))]
+    #[tokio::test]
+    #[tracing_test::traced_test]
+    async fn tracing_warns_on_revival_through_acquire() {
+        let weak = WeakCounter::new_with_capacity(1);
+        drop(weak.acquire().await);
+        assert!(!logs_contain("counter revived after draining to zero"));
+
+        let _counter = weak.acquire().await;
+        assert!(logs_contain("counter revived after draining to zero"));
+    }
+
+    #[cfg(all(feature = "tracing", feature = "std", debug_assertions))]
+    #[test]
+    #[tracing_test::traced_test]
+    fn tracing_only_warns_on_upgrades() {
+        let budget = Budget::new(1);
+        drop(budget.try_acquire().unwrap());
+        drop(budget.try_acquire().unwrap());
+
+        let counter = AtomicCounter::new();
+        for _ in 0..2 {
+            counter.increment();
+            counter.decrement();
+        }
+
+        let mut counter = Counter::new();
+        let weak = counter.downgrade_ref();
+        counter.set_size(0);
+        counter.set_size(1);
+        counter.set_size(0);
+        drop(counter.clone_with_size(1));
+        assert!(weak.is_empty());
+        assert!(!logs_contain("counter revived after draining to zero"));
+    }
+
+    #[test]
+    #[cfg(feature = "diagnostics")]
+    fn count_checked_reports_over_release() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade_with_size(2);
+        assert_eq!(weak.count_checked(), Ok(2));
+
+        weak.release(5);
+        assert_eq!(
+            weak.count_checked(),
+            Err(Desync {
+                added: 2,
+                subtracted: 5
+            })
+        );
+        weak.reset_to(2);
+        drop(counter);
+    }
+
+    #[test]
+    #[cfg(feature = "history")]
+    fn recent_counts_keep_the_latest_changes() {
+        let weak = WeakCounter::new();
+        let first = weak.spawn_upgrade();
+        let second = first.clone();
+        drop(first);
+        drop(second);
+        assert_eq!(weak.recent_counts(), [0, 1, 2, 1, 0]);
+
+        for _ in 0..20 {
+            drop(weak.spawn_upgrade_with_size(3));
+        }
+        let recent = weak.recent_counts();
+        assert_eq!(recent.len(), 32);
+        assert_eq!(recent[..2], [3, 0]);
+        assert_eq!(recent[30..], [3, 0]);
+    }
+
+    #[test]
+    #[cfg(feature = "diagnostics")]
+    fn lifetime_stats_track_totals() {
+        let counter = Counter::new_with_size(2);
+        let weak = counter.downgrade_ref();
+        assert_eq!(weak.lifetime_stats(), (2, 0));
+
+        for _ in 0..3 {
+            drop(weak.spawn_upgrade_with_size(4));
+        }
+        let clone = counter.clone();
+        assert_eq!(weak.lifetime_stats(), (16, 12));
+
+        drop((counter, clone));
+        let (added, subbed) = weak.lifetime_stats();
+        assert_eq!((added, subbed), (16, 16));
+        assert_eq!(added - subbed, weak.count() as u64);
+    }
+
+    #[test]
+    fn default_works() {
+        assert_eq!(Counter::default().count(), 1);
+        assert_eq!(WeakCounter::default().count(), 0);
+    }
+
+    #[test]
+    fn builder_combines_settings() {
+        let counter = Counter::builder().name("db_pool").size(3).build();
+        assert_eq!(counter.size(), 3);
+        assert_eq!(
+            counter.to_string(),
+            "Counter(name=db_pool, count=3, size=3)"
+        );
+
+        let drains = Arc::new(AtomicUsize::new(0));
+        let on_empty = drains.clone();
+        let weak = CounterBuilder::new()
+            .name("workers")
+            .capacity(2)
+            .on_empty(move || {
+                on_empty.fetch_add(1, Ordering::SeqCst);
+            })
+            .build_weak();
+        assert_eq!(weak.capacity(), Some(2));
+        assert_eq!(weak.to_string(), "WeakCounter(name=workers, count=0/2)");
+
+        let first = weak.spawn_upgrade();
+        let second = weak.spawn_upgrade();
+        assert!(weak.try_spawn_upgrade().is_none());
+        assert_eq!(
+            first.to_string(),
+            "Counter(name=workers, count=2/2 full, size=1)"
+        );
+        drop((first, second));
+        assert_eq!(drains.load(Ordering::SeqCst), 1);
+
+        assert_eq!(
+            CounterBuilder::new().build().to_string(),
+            "Counter(count=1, size=1)"
+        );
+    }
+
+    #[test]
+    fn display_shows_size() {
+        let counter = Counter::new_with_size(5);
+        let _clone = counter.clone();
+        assert_eq!(counter.to_string(), "Counter(count=10, size=5)");
+        assert_eq!(counter.downgrade_ref().to_string(), "WeakCounter(count=10)");
+    }
+
+    #[test]
+    fn bounded_upgrades_hold_the_cap_under_contention() {
+        const CAPACITY: usize = 3;
+        let weak = WeakCounter::new_with_capacity(CAPACITY);
+        let held = Arc::new(AtomicUsize::new(0));
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let weak = weak.clone();
+                let held = Arc::clone(&held);
+                thread::spawn(move || {
+                    let mut acquired = 0;
+                    while acquired < 1000 {
+                        if let Some(counter) = weak.try_spawn_upgrade() {
+                            let now = held.fetch_add(1, Ordering::SeqCst) + 1;
+                            assert!(now <= CAPACITY && weak.count() <= CAPACITY);
+                            held.fetch_sub(1, Ordering::SeqCst);
+                            drop(counter);
+                            acquired += 1;
+                        }
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_release_async_awaits_the_teardown() {
+        let torn_down = Arc::new(AtomicUsize::new(0));
+        let counter = {
+            let torn_down = Arc::clone(&torn_down);
+            Counter::new_with_async_last_drop(1, move || async move {
+                sleep(Duration::from_millis(10)).await;
+                torn_down.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let weak = counter.downgrade_ref();
+        let clone = counter.clone();
+
+        clone.release_async().await;
+        assert_eq!(torn_down.load(Ordering::SeqCst), 0);
+        counter.release_async().await;
+        assert_eq!(torn_down.load(Ordering::SeqCst), 1);
+
+        weak.spawn_upgrade().release_async().await;
+        assert_eq!(torn_down.load(Ordering::SeqCst), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_as_weak_borrows_the_count() {
+        fn busy(view: WeakCounterRef) -> bool {
+            !view.is_empty()
+        }
+
+        let mut counter = Counter::new_with_size(2);
+        {
+            let view = counter.as_weak();
+            let _clone = counter.clone();
+            assert_eq!(view.count(), 4);
+            assert!(busy(view));
+            assert_eq!(view.to_weak().weak_count(), 1);
+        }
+        assert_eq!(counter.as_weak().count(), 2);
+
+        counter.set_size(0);
+        counter.as_weak().wait_for_empty().await;
+    }
+
+    #[test]
+    fn merge_combines_handles_of_one_count() {
+        let counter = Counter::new_with_size(2);
+        let clone = counter.clone();
+        let mut merged = counter.merge(clone).unwrap();
+        assert_eq!(merged.size(), 4);
+        assert_eq!(merged.count(), 4);
+        assert!(merged.get_mut().is_some());
+
+        let (merged, other) = merged.merge(Counter::new()).unwrap_err();
+        assert_eq!((merged.count(), other.count()), (4, 1));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_new_with_count_starts_at_count() {
+        let weak = WeakCounter::new_with_count(2);
+        assert_eq!(weak.count(), 2);
+        assert!(!weak.try_wait_for_empty());
+
+        let waiter = {
+            let weak = weak.clone();
+            tokio::spawn(async move { weak.wait_for_empty().await })
+        };
+        weak.release(1);
+        sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+        weak.release(1);
+        waiter.await.unwrap();
+
+        WeakCounter::new_with_count(0).wait_for_empty().await;
+    }
+
+    #[test]
+    fn into_inner_arc_shares_the_atomic() {
+        let counter = Counter::from_arc(Arc::new(AtomicUsize::new(0)), 1);
+        let weak = counter.downgrade_ref();
+        let count = weak.clone().into_inner_arc().unwrap();
+        count.fetch_add(2, Ordering::SeqCst);
+        assert_eq!(weak.count(), 3);
+
+        count.fetch_sub(2, Ordering::SeqCst);
+        drop(counter);
+        assert!(weak.is_empty());
+        assert!(WeakCounter::new().into_inner_arc().is_none());
+    }
+
+    #[test]
+    fn split_conserves_the_count() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade_with_size(10);
+        let pieces = counter.split(3);
+        let sizes: Vec<usize> = pieces.iter().map(Counter::size).collect();
+        assert_eq!(sizes, [4, 3, 3]);
+        assert_eq!(weak.count(), 10);
+
+        let mut pieces = pieces.into_iter();
+        let small = pieces.next().unwrap().split(6);
+        assert_eq!(small.iter().map(Counter::size).sum::<usize>(), 4);
+        assert_eq!(weak.count(), 10);
+        drop(pieces);
+        assert_eq!(weak.count(), 4);
+        drop(small);
+        assert!(weak.is_empty());
+    }
+
+    #[test]
+    fn counter_batch_releases_together() {
+        let weak = WeakCounter::new();
+        let mut batch: CounterBatch = weak.spawn_upgrade_many(2).into();
+        batch.push(weak.spawn_upgrade_with_size(3));
+        batch.extend((0..2).map(|_| weak.spawn_upgrade()));
+        assert_eq!(batch.len(), 5);
+        assert_eq!(batch.contributed(), 7);
+        assert_eq!(weak.count(), 7);
+
+        let other = batch.clone();
+        assert_eq!(weak.count(), 14);
+        drop(other);
+        batch.drain_all();
+        assert!(weak.is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_all_and_any_empty() {
+        let weaks: Vec<WeakCounter> = (0..3).map(|_| WeakCounter::new()).collect();
+        for (i, weak) in weaks.iter().enumerate() {
+            let counter = weak.spawn_upgrade();
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(20 * (3 - i as u64))).await;
+                drop(counter);
+            });
+        }
+
+        assert_eq!(wait_for_any_empty(&weaks).await, 2);
+        assert!(weaks[0].count() == 1 && weaks[1].count() == 1);
+        wait_for_all_empty(&weaks).await;
+        assert!(weaks.iter().all(WeakCounter::is_empty));
+        assert_eq!(wait_for_any_empty(&weaks).await, 0);
+        wait_for_all_empty::<usize>(&[]).await;
+    }
+
+    #[test]
+    fn get_mut_only_for_the_sole_handle() {
+        let mut counter = Counter::new();
+        *counter.get_mut().unwrap() += 2;
+        assert_eq!(counter.count(), 3);
+
+        let clone = counter.clone();
+        assert_eq!(counter.get_mut(), None);
+        drop(clone);
+        let weak = counter.downgrade_ref();
+        assert_eq!(counter.get_mut(), None);
+        drop(weak);
+        assert_eq!(counter.get_mut(), Some(&mut 3));
+        *counter.get_mut().unwrap() = 1;
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn monotonic_count_only_increases() {
+        let counter = Counter::new_monotonic();
+        let weak = counter.downgrade_ref();
+        let mut last = weak.count();
+        for _ in 0..3 {
+            drop(counter.clone());
+            drop(weak.spawn_upgrade_with_size(2));
+            assert!(weak.count() > last);
+            last = weak.count();
+        }
+        drop(counter);
+        assert_eq!(weak.count(), 10);
+        assert!(!weak.try_wait_for_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_capacity_waits_for_room() {
+        let weak = WeakCounter::new_with_capacity(4);
+        assert!(weak.wait_for_capacity(4).await);
+        assert!(!weak.wait_for_capacity(5).await);
+
+        let mut counters = weak.spawn_upgrade_many(3);
+        assert!(weak.wait_for_capacity(1).await);
+
+        let waiter = {
+            let weak = weak.clone();
+            tokio::spawn(async move { weak.wait_for_capacity(3).await })
+        };
+        sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        counters.pop();
+        sleep(Duration::from_millis(20)).await;
+        assert!(!waiter.is_finished());
+
+        counters.pop();
+        assert!(waiter.await.unwrap());
+        assert_eq!(weak.count(), 1);
+    }
+
+    #[test]
+    fn display_shows_utilization() {
+        let weak = WeakCounter::new_with_capacity(4);
+        let _counters = weak.spawn_upgrade_many(3);
+        assert_eq!(weak.to_string(), "WeakCounter(count=3/4)");
+
+        let counter = weak.spawn_upgrade();
+        assert_eq!(weak.to_string(), "WeakCounter(count=4/4 full)");
+        assert_eq!(counter.to_string(), "Counter(count=4/4 full, size=1)");
+    }
+
+    #[test]
+    fn debug_shows_settings() {
+        let counter = CounterBuilder::new()
+            .name("db")
+            .capacity(16)
+            .size(3)
+            .build();
+        assert_eq!(
+            format!("{:?}", counter),
+            r#"Counter { count: 3, size: 3, capacity: Some(16), name: Some("db") }"#
+        );
+        assert_eq!(
+            format!("{:?}", WeakCounter::new()),
+            "WeakCounter { count: 0, capacity: None, name: None }"
+        );
+    }
+
+    #[test]
+    fn from_arc_shares_the_count() {
+        let count = Arc::new(AtomicUsize::new(1));
+        let counter = Counter::from_arc(Arc::clone(&count), 2);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+
+        let clone = counter.clone();
+        assert_eq!(count.load(Ordering::SeqCst), 5);
+
+        count.fetch_sub(1, Ordering::SeqCst);
+        assert_eq!(counter.count(), 4);
+
+        drop(clone);
+        drop(counter);
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn as_raw_atomic_tracks_count() {
+        let counter = Counter::new_with_size(2);
+        let weak = counter.downgrade_ref();
+        let raw = counter.as_raw_atomic();
+        assert_eq!(raw, weak.as_raw_atomic());
+
+        drop(counter);
+        // weak keeps the atomic alive.
+        assert_eq!(unsafe { (*raw).load(Ordering::Acquire) }, 0);
+        let _counter = weak.spawn_upgrade_with_size(3);
+        assert_eq!(unsafe { (*raw).load(Ordering::Acquire) }, 3);
+    }
+
+    #[test]
+    fn count_relaxed_works() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade_with_size(3);
+        assert_eq!(weak.count_relaxed(), 3);
+        assert_eq!(counter.count_relaxed(), 3);
+        drop(counter);
+        assert_eq!(weak.count_relaxed(), 0);
+    }
+
+    #[test]
+    fn conversions_work() {
+        let counter = Counter::new();
+        let weak: WeakCounter = counter.into();
+        assert_eq!(weak.count(), 0);
+
+        let counter = Counter::try_from(weak).unwrap();
+        assert_eq!(counter.count(), 1);
+
+        let closeable = WeakCounter::new_closeable();
+        drop(closeable.spawn_upgrade());
+        let closeable = Counter::try_from(closeable).unwrap_err();
+        assert_eq!(closeable.count(), 0);
+    }
+
+    #[test]
+    fn scope_releases_on_return_and_panic() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.scope(|| weak.count()), 1);
+        assert_eq!(weak.count(), 0);
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            weak.scope(|| panic!("boom"))
+        }));
+        assert!(result.is_err());
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_scope_async_releases_on_completion() {
+        let weak = WeakCounter::new();
+        let count = weak
+            .scope_async(async {
+                sleep(Duration::from_millis(1)).await;
+                weak.count()
+            })
+            .await;
+        assert_eq!(count, 1);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn clone_many_adds_once() {
+        let counter = Counter::new_with_size(3);
+        let clones = counter.clone_many(4);
+        assert_eq!(clones.len(), 4);
+        assert_eq!(counter.count(), 3 + 3 * 4);
+        assert!(clones.iter().all(|clone| clone.size() == 3));
+        drop(clones);
+        assert_eq!(counter.count(), 3);
+        assert!(counter.clone_many(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "clone_many overflowed the count")]
+    fn clone_many_detects_overflow() {
+        let counter = Counter::new_with_size(usize::MAX / 2);
+        let _clones = counter.clone_many(3);
+    }
+
+    #[test]
+    fn spawn_upgrade_many_works() {
+        let weak = WeakCounter::new();
+        let counters = weak.spawn_upgrade_many(3);
+        assert_eq!(counters.len(), 3);
+        assert_eq!(weak.count(), 3);
+
+        let sized = weak.spawn_upgrade_many_with_size(2, 4);
+        assert_eq!(sized.len(), 2);
+        assert_eq!(weak.count(), 3 + 2 * 4);
+        drop((counters, sized));
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn try_spawn_upgrade_many_respects_capacity() {
+        let weak = WeakCounter::new_with_capacity(5);
+        let _held = weak.spawn_upgrade_with_size(2);
+
+        assert!(weak.try_spawn_upgrade_many(4).is_none());
+        assert!(weak.try_spawn_upgrade_many_with_size(2, 2).is_none());
+        assert_eq!(weak.count(), 2);
+
+        let batch = weak.try_spawn_upgrade_many(3).unwrap();
+        assert_eq!(batch.len(), 3);
+        assert_eq!(weak.count(), 5);
+        assert!(weak.try_spawn_upgrade_many(1).is_none());
+        assert_eq!(weak.try_spawn_upgrade_many(0).unwrap().len(), 0);
+    }
+
+    fn check_width<T: CountInt + From<u8>>() {
+        let weak = GenericWeakCounter::<T>::new_with_capacity(T::from(3));
+        let counter = weak.spawn_upgrade_with_size(T::from(2));
+        assert_eq!(weak.count(), T::from(2));
+        assert!(weak.try_spawn_upgrade_with_size(T::from(2)).is_none());
+        let batch = weak.try_spawn_upgrade_many(1).unwrap();
+        assert_eq!(weak.count(), T::from(3));
+        drop((counter, batch));
+        assert!(weak.is_empty());
+        assert_eq!(weak.peak(), T::from(3));
+
+        let closeable = GenericWeakCounter::<T>::new_closeable();
+        drop(closeable.spawn_upgrade());
+        assert!(closeable.try_spawn_upgrade().is_none());
+        assert!(closeable.is_empty());
+    }
+
+    #[test]
+    fn generic_widths_work() {
+        check_width::<usize>();
+        check_width::<u64>();
+        check_width::<u32>();
+
+        let weak = WeakCounter32::new_closeable();
+        assert!(weak.try_spawn_upgrade_with_size(u32::MAX).is_none());
+        let counter = weak.spawn_upgrade_with_size(u32::MAX / 2);
+        assert_eq!(counter.count(), u32::MAX / 2);
+    }
+
+    #[test]
+    fn counter64_exceeds_32_bits() {
+        let weak = WeakCounter64::new();
+        let counter = weak.spawn_upgrade_with_size(5_000_000_000);
+        let _clone = counter.clone();
+        assert_eq!(weak.count(), 10_000_000_000);
+        assert_eq!(counter.size(), 5_000_000_000);
+        assert_eq!(
+            counter.to_string(),
+            "Counter(count=10000000000, size=5000000000)"
+        );
+
+        drop(counter);
+        assert_eq!(weak.count(), 5_000_000_000);
+        assert_eq!(weak.peak(), 10_000_000_000);
+    }
+
+    #[test]
+    fn leak_keeps_count_elevated() {
+        let weak = WeakCounter::new();
+        weak.spawn_upgrade_with_size(2).leak();
+        assert_eq!(weak.count(), 2);
+
+        let counter = weak.spawn_upgrade();
+        drop(counter);
+        assert_eq!(weak.count(), 2);
+        assert!(!weak.is_empty());
+    }
+
+    #[test]
+    fn downgrade_keep_count_keeps_size() {
+        let counter = Counter::new_with_size(3);
+        let weak = counter.downgrade_keep_count();
+        assert_eq!(weak.count(), 3);
+        assert_eq!(weak.weak_count(), 1);
+
+        weak.release(3);
+        assert!(weak.is_empty());
+    }
+
+    #[test]
+    fn is_empty_works() {
+        let weak = WeakCounter::new();
+        assert!(weak.is_empty());
+
+        let counter = weak.spawn_upgrade();
+        assert!(!weak.is_empty());
+        assert!(!counter.is_empty());
+
+        drop(counter);
+        assert!(weak.is_empty());
+    }
+
+    #[test]
+    fn is_last_flips_as_clones_drop() {
+        let counter = Counter::new_with_size(2);
+        assert!(counter.is_last());
+        assert_eq!(counter.count_excluding_self(), 0);
+
+        let clone1 = counter.clone();
+        let clone2 = counter.clone();
+        assert!(!counter.is_last());
+        assert_eq!(counter.count_excluding_self(), 4);
+
+        drop(clone1);
+        assert!(!clone2.is_last());
+        drop(counter);
+        assert!(clone2.is_last());
+        assert_eq!(clone2.count_excluding_self(), 0);
+    }
+
+    #[test]
+    fn different_sizes_work() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.count(), 0);
+
+        let counter = weak.spawn_upgrade_with_size(5);
+        assert_eq!(weak.count(), 5);
+
+        {
+            let _counter1 = counter.clone();
+            assert_eq!(weak.count(), 10);
+            let _counter2 = weak.spawn_upgrade();
+            assert_eq!(weak.count(), 11);
+        }
+
+        assert_eq!(weak.count(), 5);
+    }
+
+    #[test]
+    fn new_with_size_drains_to_zero() {
+        let counter = Counter::new_with_size(5);
+        assert_eq!(counter.count(), 5);
+
+        let clone = counter.clone();
+        assert_eq!(counter.count(), 10);
+        drop(clone);
+
+        let weak = counter.downgrade();
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn saturating_clamps_at_zero() {
+        let counter = Counter::new_saturating(1);
+        let weak = counter.downgrade_ref();
+        let mismatched = Counter {
+            counter: counter.counter.clone(),
+            size: 5,
+        };
+        assert_eq!(weak.count(), 1);
+
+        drop(mismatched);
+        assert_eq!(weak.count(), 0);
+        assert!(
+            weak.wait_for_empty_timeout(Duration::from_millis(100))
+                .await
+        );
+
+        drop(counter);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn on_empty_fires_on_each_drain() {
+        let drains = Arc::new(AtomicUsize::new(0));
+        let drains_clone = Arc::clone(&drains);
+        let counter = Counter::new_with_on_empty(1, move || {
+            drains_clone.fetch_add(1, Ordering::SeqCst);
+        });
+        let clone = counter.clone();
+        let weak = clone.downgrade_ref();
+
+        drop(counter);
+        assert_eq!(drains.load(Ordering::SeqCst), 0);
+        drop(clone);
+        assert_eq!(drains.load(Ordering::SeqCst), 1);
+
+        drop(weak.spawn_upgrade());
+        assert_eq!(drains.load(Ordering::SeqCst), 2);
+
+        let _counter1 = weak.spawn_upgrade();
+        drop(weak.spawn_upgrade());
+        assert_eq!(drains.load(Ordering::SeqCst), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn last_drop_runs_once() {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let counter = {
+            let runs = Arc::clone(&runs);
+            Counter::new_with_last_drop(1, move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+            })
+        };
+        let clones = counter.clone_many(3);
+        let weak = counter.downgrade();
+        assert_eq!(runs.load(Ordering::SeqCst), 0);
+
+        drop(clones);
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        drop(weak.spawn_upgrade());
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn set_size_adjusts_count() {
+        let weak = WeakCounter::new();
+        let mut counter = weak.spawn_upgrade_with_size(2);
+        let _other = weak.spawn_upgrade();
+
+        counter.set_size(5);
+        assert_eq!(counter.size(), 5);
+        assert_eq!(weak.count(), 6);
+
+        counter.set_size(0);
+        assert_eq!(weak.count(), 1);
+
+        counter.set_size(3);
+        drop(counter);
+        assert_eq!(weak.count(), 1);
+    }
+
+    #[test]
+    fn add_and_sub_assign_adjust_size() {
+        let mut counter = Counter::new();
+        let weak = counter.downgrade_ref();
+
+        counter += 2;
+        assert_eq!(counter.size(), 3);
+        assert_eq!(weak.count(), 3);
+
+        let big = counter.clone() + 4;
+        assert_eq!(big.size(), 7);
+        assert_eq!(weak.count(), 10);
+
+        counter -= 3;
+        assert_eq!(counter.size(), 0);
+        assert_eq!(weak.count(), 7);
+        drop(big);
+        assert!(weak.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "subtracted more than the Counter's size")]
+    fn sub_assign_panics_below_zero() {
+        let mut counter = Counter::new_with_size(2);
+        counter -= 3;
+    }
+
+    #[test]
+    fn try_clone_detects_overflow() {
+        let weak = WeakCounter::new();
+
+        let counter = weak.spawn_upgrade_with_size(usize::MAX / 2);
+        let clone = counter.try_clone().unwrap();
+        assert_eq!(weak.count(), usize::MAX - 1);
+
+        assert_eq!(counter.try_clone().unwrap_err(), CounterOverflow);
+        assert_eq!(weak.count(), usize::MAX - 1);
+
+        drop(clone);
+        assert_eq!(weak.count(), usize::MAX / 2);
+        assert!(counter.try_clone().is_ok());
+    }
+
+    #[test]
+    fn take_while_under_stops_at_threshold() {
+        let weak = WeakCounter::new_with_capacity(10);
+        let _held = weak.spawn_upgrade();
+        let mut items = vec![1, 2, 3, 4, 5].into_iter();
+
+        let taken: Vec<_> = items.by_ref().take_while_under(weak.clone(), 4).collect();
+        assert_eq!(
+            taken.iter().map(|(item, _)| *item).collect::<Vec<_>>(),
+            [1, 2, 3]
+        );
+        assert_eq!(weak.count(), 4);
+        // The item past the threshold is left in the iterator.
+        assert_eq!(items.next(), Some(4));
+
+        drop(taken);
+        assert_eq!(weak.count(), 1);
+
+        // Running out of items gives the last slot back.
+        let taken: Vec<_> = items.take_while_under(weak.clone(), 4).collect();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(weak.count(), 2);
+    }
+
+    #[test]
+    fn clone_with_size_subtracts_its_own_size() {
+        let parent = Counter::new_with_size(2);
+        let weak = parent.downgrade_ref();
+        let child = parent.clone_with_size(5);
+        assert_eq!(child.size(), 5);
+        assert_eq!(weak.count(), 7);
+
+        drop(parent);
+        assert_eq!(weak.count(), 5);
+        drop(child);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn saturating_clone_caps_at_max() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade_with_size(usize::MAX / 2);
+        let clone = counter.clone();
+        assert_eq!(weak.count(), usize::MAX - 1);
+
+        let partial = counter.saturating_clone();
+        assert_eq!(partial.size(), 1);
+        assert_eq!(weak.count(), usize::MAX);
+        let empty = counter.saturating_clone();
+        assert_eq!(empty.size(), 0);
+        assert_eq!(weak.count(), usize::MAX);
+
+        drop(empty);
+        assert_eq!(weak.count(), usize::MAX);
+        drop(partial);
+        assert_eq!(weak.count(), usize::MAX - 1);
+        drop(clone);
+        assert_eq!(counter.saturating_clone().size(), usize::MAX / 2);
+    }
+
+    #[test]
+    fn spawn_upgrade_checked_detects_overflow() {
+        let weak = WeakCounter::new();
+
+        let counter = weak.spawn_upgrade_checked(usize::MAX - 1).unwrap();
+        assert_eq!(weak.spawn_upgrade_checked(2).unwrap_err(), usize::MAX - 1);
+        assert_eq!(weak.count(), usize::MAX - 1);
+
+        let last = weak.spawn_upgrade_checked(1).unwrap();
+        assert_eq!(weak.count(), usize::MAX);
+        assert_eq!(weak.spawn_upgrade_checked(1).unwrap_err(), usize::MAX);
+
+        drop(last);
+        drop(counter);
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[test]
+    fn size_is_exposed() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.spawn_upgrade().size(), 1);
+
+        let counter = weak.spawn_upgrade_with_size(5);
+        assert_eq!(counter.size(), 5);
+        assert_eq!(counter.clone().size(), 5);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_release_drains_manual_holds() {
+        let weak = WeakCounter::new();
+        let mut events = weak.empty_events();
+        for counter in weak.spawn_upgrade_many(3) {
+            counter.leak();
+        }
+        assert_eq!(weak.count(), 3);
+
+        weak.release(2);
+        assert_eq!(weak.count(), 1);
+        assert!(futures::poll!(events.next()).is_pending());
+
+        weak.release(1);
+        weak.wait_for_empty().await;
+        assert_eq!(events.next().await, Some(()));
+
+        // Over-releasing clamps at zero and isn't another drain.
+        weak.release(1);
+        assert_eq!(weak.count(), 0);
+        assert!(futures::poll!(events.next()).is_pending());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_reset_to_overrides_count() {
+        let weak = WeakCounter::new();
+        let mut events = weak.empty_events();
+        weak.spawn_upgrade_with_size(3).leak();
+        assert_eq!(weak.count(), 3);
+
+        weak.reset_to(0);
+        assert_eq!(weak.count(), 0);
+        assert_eq!(events.next().await, Some(()));
+        weak.wait_for_empty().await;
+
+        weak.reset_to(5);
+        assert_eq!(weak.count(), 5);
+        assert!(futures::poll!(weak.empty_future()).is_pending());
+        assert!(futures::poll!(events.next()).is_pending());
+
+        weak.reset_to(0);
+        assert_eq!(events.next().await, Some(()));
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_try_wait_for_empty_agrees_with_waiters() {
+        let weak = WeakCounter::new();
+        let mut waiter = weak.empty_future();
+        assert!(weak.try_wait_for_empty());
+
+        let counter = weak.spawn_upgrade_with_size(2);
+        let mut waiter2 = weak.empty_future();
+        assert!(!weak.try_wait_for_empty());
+        assert!(futures::poll!(&mut waiter2).is_pending());
+
+        let clone = counter.clone();
+        drop(counter);
+        assert!(!weak.try_wait_for_empty());
+        assert!(futures::poll!(&mut waiter2).is_pending());
+
+        drop(clone);
+        assert!(weak.try_wait_for_empty());
+        assert_eq!(weak.try_wait_for_empty(), weak.count() == 0);
+        assert!(futures::poll!(&mut waiter).is_ready());
+        assert!(futures::poll!(&mut waiter2).is_ready());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_fair_wakes_in_order() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let mut first = Box::pin(weak.wait_for_empty_fair());
+        let mut second = Box::pin(weak.wait_for_empty_fair());
+        let mut third = Box::pin(weak.wait_for_empty_fair());
+        assert!(futures::poll!(&mut first).is_pending());
+        assert!(futures::poll!(&mut second).is_pending());
+        assert!(futures::poll!(&mut third).is_pending());
+
+        drop(counter);
+        assert!(futures::poll!(&mut third).is_pending());
+        assert!(futures::poll!(&mut first).is_ready());
+        // Dropping a woken waiter passes the wakeup on.
+        drop(second);
+        assert!(futures::poll!(&mut third).is_ready());
+
+        // Resolves right away while the count is zero.
+        weak.wait_for_empty_fair().await;
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_fair_under_contention() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let waiters: Vec<_> = (0..32)
+            .map(|_| {
+                let weak = weak.clone();
+                tokio::spawn(async move { weak.wait_for_empty_fair().await })
+            })
+            .collect();
+
+        // Bounce the count off zero while the waiters queue up.
+        for _ in 0..100 {
+            drop(weak.spawn_upgrade());
+            tokio::task::yield_now().await;
+        }
+        drop(counter);
+
+        for waiter in waiters {
+            tokio::time::timeout(Duration::from_secs(5), waiter)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_works() {
+        let start = Instant::now();
+        let weak = WeakCounter::new();
+
+        let counter1 = weak.spawn_upgrade();
+        let counter2 = counter1.clone();
+        let counter3 = counter2.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(250)).await;
+            drop(counter1);
+
+            sleep(Duration::from_millis(500)).await;
+            drop(counter2);
+
+            sleep(Duration::from_millis(100)).await;
+            drop(counter3);
+        });
+
+        weak.wait_for_empty().await;
+        let elapsed = start.elapsed();
+
+        assert!(elapsed >= Duration::from_millis(850));
+        assert!(elapsed < Duration::from_millis(900));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_for_empty_blocking_wakes_every_thread() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let waiters: Vec<_> = (0..8)
+            .map(|_| {
+                let weak = weak.clone();
+                thread::spawn(move || weak.wait_for_empty_blocking())
+            })
+            .collect();
+
+        thread::sleep(Duration::from_millis(10));
+        drop(counter);
+        for waiter in waiters {
+            waiter.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_for_empty_blocking_works() {
+        let start = Instant::now();
+        let weak = WeakCounter::new();
+
+        let handles: Vec<_> = (1..=3)
+            .map(|i| {
+                let counter = weak.spawn_upgrade();
+                thread::spawn(move || {
+                    thread::sleep(Duration::from_millis(100 * i));
+                    drop(counter);
+                })
+            })
+            .collect();
+
+        weak.wait_for_empty_blocking();
+        let elapsed = start.elapsed();
+
+        assert_eq!(weak.count(), 0);
+        assert!(elapsed >= Duration::from_millis(300));
+        assert!(elapsed < Duration::from_millis(600));
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_at_least_works() {
+        let weak = WeakCounter::new();
+
+        let weak2 = weak.clone();
+        let handle = tokio::spawn(async move {
+            let mut counters = Vec::new();
+            for _ in 0..3 {
+                sleep(Duration::from_millis(50)).await;
+                counters.push(weak2.spawn_upgrade());
+            }
+            sleep(Duration::from_millis(200)).await;
+        });
+
+        futures::join!(weak.wait_for_at_least(2), weak.wait_for_at_least(3));
+        assert_eq!(weak.count(), 3);
+        handle.await.unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_acquire_respects_capacity() {
+        let weak = WeakCounter::new_with_capacity(2);
+        let held = Arc::new(AtomicUsize::new(0));
+
+        let tasks: Vec<_> = (0..10)
+            .map(|_| {
+                let weak = weak.clone();
+                let held = Arc::clone(&held);
+                tokio::spawn(async move {
+                    let counter = weak.acquire().await;
+                    assert!(held.fetch_add(1, Ordering::SeqCst) < 2);
+                    assert!(weak.count() <= 2);
+                    sleep(Duration::from_millis(10)).await;
+                    held.fetch_sub(1, Ordering::SeqCst);
+                    drop(counter);
+                })
+            })
+            .collect();
+
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_acquire_when_below_holds_threshold() {
+        let weak = WeakCounter::new();
+        let tasks: Vec<_> = (0..16)
+            .map(|_| {
+                let weak = weak.clone();
+                tokio::spawn(async move {
+                    for _ in 0..50 {
+                        let counter = weak.acquire_when_below(4, 2).await;
+                        // Admitted below 4 and added 2, never exceeding 5.
+                        assert!(weak.count() <= 5);
+                        tokio::task::yield_now().await;
+                        drop(counter);
+                    }
+                })
+            })
+            .collect();
+        for task in tasks {
+            task.await.unwrap();
+        }
+        assert_eq!(weak.count(), 0);
+        assert!(weak.peak() <= 5);
+
+        let held = weak.spawn_upgrade_with_size(4);
+        let pending = weak.acquire_when_below(4, 1);
+        futures::pin_mut!(pending);
+        assert!(futures::poll!(pending.as_mut()).is_pending());
+        drop(held);
+        assert_eq!(pending.await.count(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_acquire_is_cancellation_safe() {
+        let weak = WeakCounter::new_with_capacity(1);
+        let counter = weak.acquire().await;
+
+        let timed_out = tokio::time::timeout(Duration::from_millis(50), weak.acquire()).await;
+        assert!(timed_out.is_err());
+        assert_eq!(weak.count(), 1);
+
+        drop(counter);
+        let _counter = weak.acquire().await;
+        assert_eq!(weak.count(), 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_empty_events_counts_drains() {
+        let weak = WeakCounter::new();
+        let mut events = weak.empty_events();
+
+        drop(weak.spawn_upgrade());
+        drop(weak.spawn_upgrade_with_size(2));
+        let counter = weak.spawn_upgrade();
+        assert_eq!(events.next().await, Some(()));
+        assert_eq!(events.next().await, Some(()));
+        assert!(futures::poll!(events.next()).is_pending());
+
+        // A drop that leaves the count above zero is not a drain.
+        drop(counter.clone());
+        assert!(futures::poll!(events.next()).is_pending());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(counter);
+        });
+        assert_eq!(events.next().await, Some(()));
+        assert!(futures::poll!(events.next()).is_pending());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_observers_await_changes_independently() {
+        let weak = WeakCounter::new();
+        let mut observer1 = weak.observer();
+        let mut observer2 = observer1.clone();
+        assert_eq!(observer1.last(), 0);
+        assert!(futures::poll!(Box::pin(observer1.changed())).is_pending());
+
+        let counter = weak.spawn_upgrade_with_size(2);
+        assert_eq!(observer1.changed().await, 2);
+        assert!(futures::poll!(Box::pin(observer1.changed())).is_pending());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(counter);
+        });
+        assert_eq!(observer1.changed().await, 0);
+
+        // observer2 never saw 2, and catches up to the latest count.
+        assert_eq!(observer2.last(), 0);
+        let _counter = weak.spawn_upgrade();
+        assert_eq!(observer2.changed().await, 1);
+        assert_eq!(observer1.changed().await, 1);
+        assert_eq!(weak.weak_count(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_count_stream_works() {
+        let weak = WeakCounter::new();
+        let mut stream = weak.count_stream();
+        assert_eq!(stream.next().await, Some(0));
+
+        let counter1 = weak.spawn_upgrade();
+        assert_eq!(stream.next().await, Some(1));
+
+        let counter2 = counter1.clone();
+        let counter3 = counter2.clone();
+        assert_eq!(stream.next().await, Some(3));
+
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            drop(counter1);
+            drop(counter2);
+            drop(counter3);
+        });
+
+        let mut last = 3;
+        while last != 0 {
+            let count = stream.next().await.unwrap();
+            assert!(count < last);
+            last = count;
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_timeout_drains() {
+        let weak = WeakCounter::new();
+
+        let counter = weak.spawn_upgrade();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            drop(counter);
+        });
+
+        assert!(
+            weak.wait_for_empty_timeout(Duration::from_millis(500))
+                .await
+        );
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_timeout_times_out() {
+        let start = Instant::now();
+        let weak = WeakCounter::new();
+
+        let _counter = weak.spawn_upgrade();
+        assert!(
+            !weak
+                .wait_for_empty_timeout(Duration::from_millis(200))
+                .await
+        );
+
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(500));
+        assert_eq!(weak.count(), 1);
+    }
+
+    #[cfg(all(
+        feature = "std",
+        not(any(feature = "tokio-timer", feature = "async-std-timer"))
+    ))]
+    #[tokio::test]
+    async fn test_wait_for_empty_timeout_shares_the_timer_thread() {
+        let weak = WeakCounter::new();
+        let _counter = weak.spawn_upgrade();
+
+        // Queued behind a later deadline, the shorter timeout still fires first.
+        let long = tokio::spawn({
+            let weak = weak.clone();
+            async move { weak.wait_for_empty_timeout(Duration::from_secs(60)).await }
+        });
+        sleep(Duration::from_millis(20)).await;
+
+        let start = Instant::now();
+        let timeouts = (0..100).map(|_| weak.wait_for_empty_timeout(Duration::from_millis(50)));
+        let timed_out = futures::future::join_all(timeouts).await;
+        assert!(timed_out.iter().all(|drained| !drained));
+        assert!(start.elapsed() < Duration::from_secs(5));
+
+        long.abort();
+        assert!(long.await.unwrap_err().is_cancelled());
+    }
+
+    #[cfg(feature = "tokio-timer")]
+    #[tokio::test(start_paused = true)]
+    async fn test_wait_for_empty_timeout_uses_tokio_timer() {
+        let weak = WeakCounter::new();
+        let _counter = weak.spawn_upgrade();
+
+        // Paused time auto-advances, which only tokio's timer can see.
+        let start = Instant::now();
+        assert!(!weak.wait_for_empty_timeout(Duration::from_secs(3600)).await);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(all(feature = "async-std-timer", not(feature = "tokio-timer")))]
+    #[test]
+    fn wait_for_empty_timeout_uses_async_std_timer() {
+        async_std::task::block_on(async {
+            let weak = WeakCounter::new();
+            let counter = weak.spawn_upgrade();
+            assert!(!weak.wait_for_empty_timeout(Duration::from_millis(50)).await);
+
+            async_std::task::spawn(async move {
+                async_std::task::sleep(Duration::from_millis(50)).await;
+                drop(counter);
+            });
+            assert!(weak.wait_for_empty_timeout(Duration::from_secs(5)).await);
+        });
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_change_sees_both_directions() {
+        let weak = WeakCounter::new();
+        assert_eq!(weak.wait_for_change(5).await, 0);
+
+        let mut up = Box::pin(weak.wait_for_change(0));
+        assert!(futures::poll!(&mut up).is_pending());
+        let counter = weak.spawn_upgrade_with_size(2);
+        assert_eq!(up.await, 2);
+
+        let mut down = Box::pin(weak.wait_for_change(2));
+        assert!(futures::poll!(&mut down).is_pending());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(counter);
+        });
+        assert_eq!(down.await, 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_drain_and_close_admits_no_new_handles() {
+        let weak = WeakCounter::new_closeable();
+        let held = weak.spawn_upgrade();
+        let closing = Arc::new(AtomicUsize::new(0));
+
+        let upgrader = {
+            let weak = weak.clone();
+            let closing = Arc::clone(&closing);
+            thread::spawn(move || loop {
+                let closing = closing.load(Ordering::SeqCst) == 1;
+                let admitted = weak.try_spawn_upgrade().is_some();
+                assert!(!(closing && admitted), "upgraded after drain_and_close");
+                if closing {
+                    return;
+                }
+            })
+        };
+
+        thread::sleep(Duration::from_millis(10));
+        let mut drain = Box::pin(weak.drain_and_close());
+        assert!(futures::poll!(&mut drain).is_pending());
+        assert!(weak.has_closed());
+        closing.store(1, Ordering::SeqCst);
+        upgrader.join().unwrap();
+
+        assert!(weak.try_spawn_upgrade().is_none());
+        drop(held);
+        drain.await;
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_or_picks_the_first() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let signal = sleep(Duration::from_millis(50));
+        assert_eq!(weak.wait_for_empty_or(signal).await, Either::Right(()));
+
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(50)).await;
+            drop(counter);
+        });
+        let signal = sleep(Duration::from_secs(5));
+        assert_eq!(weak.wait_for_empty_or(signal).await, Either::Left(()));
+
+        // The drain wins when both are ready.
+        let signal = futures::future::ready(1);
+        assert_eq!(weak.wait_for_empty_or(signal).await, Either::Left(()));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn wait_for_empty_only_wakes_on_drains() {
+        struct CountWakes(AtomicUsize);
+
+        impl futures::task::ArcWake for CountWakes {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+        let waker = futures::task::waker(Arc::clone(&wakes));
+        let mut cx = std::task::Context::from_waker(&waker);
+        let wakes = || wakes.0.load(Ordering::SeqCst);
+
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let mut waiter = Box::pin(weak.wait_for_empty());
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+
+        // Churn that never reaches zero doesn't wake the waiter at all.
+        let churn = {
+            let counter = counter.clone();
+            thread::spawn(move || {
+                for _ in 0..10_000 {
+                    drop(counter.clone());
+                }
+            })
+        };
+        churn.join().unwrap();
+        assert_eq!(wakes(), 0);
+
+        // A drain that is undone before the waiter runs wakes it once, after
+        // which it parks again instead of spinning.
+        let next = weak.spawn_upgrade();
+        drop(counter);
+        assert_eq!(wakes(), 0);
+        drop(next);
+        let counter = weak.spawn_upgrade();
+        assert_eq!(wakes(), 1);
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+        assert!(waiter.as_mut().poll(&mut cx).is_pending());
+        assert_eq!(wakes(), 1);
+
+        drop(counter);
+        assert_eq!(wakes(), 2);
+        assert!(waiter.as_mut().poll(&mut cx).is_ready());
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_empty_survives_dropped_waiter() {
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+
+        let mut dropped = Box::pin(weak.wait_for_empty());
+        let kept = weak.wait_for_empty();
+        assert!(futures::poll!(dropped.as_mut()).is_pending());
+        drop(dropped);
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(counter);
+        });
+        tokio::time::timeout(Duration::from_secs(5), kept)
+            .await
+            .expect("remaining waiter was never woken");
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_wait_for_empty_under_contention() {
+        let weak = WeakCounter::new();
+        let churners: Vec<_> = (0..8)
+            .map(|_| {
+                let weak = weak.clone();
+                thread::spawn(move || {
+                    for _ in 0..10_000 {
+                        drop(weak.spawn_upgrade());
+                    }
+                })
+            })
+            .collect();
+
+        let waiters: Vec<_> = (0..4)
+            .map(|_| {
+                let weak = weak.clone();
+                tokio::spawn(async move {
+                    for _ in 0..1_000 {
+                        weak.wait_for_empty().await;
+                    }
+                })
+            })
+            .collect();
+        let blocking = {
+            let weak = weak.clone();
+            thread::spawn(move || {
+                for _ in 0..1_000 {
+                    weak.wait_for_empty_blocking();
+                }
+            })
+        };
+
+        for churner in churners {
+            churner.join().unwrap();
+        }
+        for waiter in waiters {
+            tokio::time::timeout(Duration::from_secs(10), waiter)
+                .await
+                .expect("waiter hung after the counter drained")
+                .unwrap();
+        }
+        blocking.join().unwrap();
+        assert_eq!(weak.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_empty_future_can_be_stored() {
+        struct Shutdown {
+            drained: EmptyFuture,
+        }
+
+        fn assert_send<F: Send>(_: &F) {}
+
+        let weak = WeakCounter::new();
+        let counter = weak.spawn_upgrade();
+        let mut shutdown = Shutdown {
+            drained: weak.empty_future(),
+        };
+        assert_send(&shutdown.drained);
+        drop(weak);
+
+        assert!(futures::poll!(&mut shutdown.drained).is_pending());
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            drop(counter);
+        });
+        shutdown.drained.await;
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_wait_for_empty_is_runtime_agnostic() {
+        let weak = WeakCounter::new();
+
+        let counter = weak.spawn_upgrade();
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(100));
+            drop(counter);
+        });
+
+        futures::executor::block_on(weak.wait_for_empty());
+        assert_eq!(weak.count(), 0);
+        handle.join().unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_atomic_counter_works() {
+        let counter = AtomicCounter::new();
+        counter.wait_for_empty().await;
+
+        counter.increment();
+        counter.increment();
+        let clone = counter.clone();
+        clone.decrement();
+        assert_eq!(counter.get(), 1);
+        assert!(futures::poll!(Box::pin(counter.wait_for_empty())).is_pending());
+
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            clone.decrement();
+        });
+        counter.wait_for_empty().await;
+        assert_eq!(counter.get(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_countdown_latch_works() {
+        let latch = CountdownLatch::new(4);
+        let tasks: Vec<_> = (0..4)
+            .map(|_| {
+                let latch = latch.clone();
+                tokio::spawn(async move {
+                    sleep(Duration::from_millis(10)).await;
+                    latch.count_down();
+                })
+            })
+            .collect();
+
+        latch.wait().await;
+        assert_eq!(latch.count(), 0);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        latch.count_down();
+        assert_eq!(latch.count(), 0);
+        latch.wait().await;
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_group_works() {
+        let group = WaitGroup::new();
+        let done = Arc::new(AtomicUsize::new(0));
+        for _ in 0..3 {
+            let guard = group.add();
+            let done = done.clone();
+            tokio::spawn(async move {
+                let _extra = guard.clone();
+                sleep(Duration::from_millis(10)).await;
+                done.fetch_add(1, Ordering::SeqCst);
+                drop(guard);
+            });
+        }
+
+        group.wait().await;
+        assert_eq!(done.load(Ordering::SeqCst), 3);
+        assert_eq!(group.count(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[tokio::test]
+    async fn test_wait_for_count_works() {
+        let start = Instant::now();
+        let weak = WeakCounter::new();
+
+        let counter1 = weak.spawn_upgrade();
+        let counter2 = counter1.clone();
+        let counter3 = counter2.clone();
+        tokio::spawn(async move {
+            sleep(Duration::from_millis(100)).await;
+            drop(counter1);
+
+            sleep(Duration::from_millis(100)).await;
+            drop(counter2);
+
+            sleep(Duration::from_millis(500)).await;
+            drop(counter3);
+        });
+
+        weak.wait_for_count(1).await;
+        let elapsed = start.elapsed();
+
+        assert_eq!(weak.count(), 1);
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(700));
     }
 }
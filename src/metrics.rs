@@ -0,0 +1,70 @@
+use crate::WeakCounter;
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use prometheus::core::{Collector, Desc};
+use prometheus::proto::MetricFamily;
+use prometheus::{Gauge, Registry};
+
+/// Reads the count into a gauge each time the registry is scraped.
+struct CountCollector {
+    weak: WeakCounter,
+    gauge: Gauge,
+}
+
+impl Collector for CountCollector {
+    fn desc(&self) -> Vec<&Desc> {
+        self.gauge.desc()
+    }
+
+    fn collect(&self) -> Vec<MetricFamily> {
+        self.gauge.set(self.weak.count() as f64);
+        self.gauge.collect()
+    }
+}
+
+/// Registers a gauge called `name` with `registry` that reports the count
+/// observed by `weak` on every scrape.
+///
+/// The count is only read when the registry is gathered, so updating the
+/// counter costs nothing extra. Fails if `name` is not a valid metric name
+/// or is already registered.
+pub fn register_gauge(
+    weak: WeakCounter,
+    registry: &Registry,
+    name: &str,
+) -> Result<(), prometheus::Error> {
+    let help = match weak.name() {
+        Some(counter) => format!("Current count of {}", counter),
+        None => String::from("Current count"),
+    };
+    let gauge = Gauge::new(name, help)?;
+    registry.register(Box::new(CountCollector { weak, gauge }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrape(registry: &Registry) -> f64 {
+        let families = registry.gather();
+        assert_eq!(families.len(), 1);
+        families[0].get_metric()[0].get_gauge().get_value()
+    }
+
+    #[test]
+    fn gauge_tracks_count() {
+        let registry = Registry::new();
+        let weak = WeakCounter::new();
+        register_gauge(weak.clone(), &registry, "in_flight").unwrap();
+        assert_eq!(scrape(&registry), 0.0);
+
+        let counter = weak.spawn_upgrade_with_size(3);
+        assert_eq!(scrape(&registry), 3.0);
+
+        drop(counter);
+        assert_eq!(scrape(&registry), 0.0);
+        assert!(register_gauge(weak, &registry, "in_flight").is_err());
+    }
+}
@@ -0,0 +1,41 @@
+use crate::{CountInt, GenericWeakCounter};
+
+/// Awaits changes to a count on demand, see [`WeakCounter::observer`].
+///
+/// Each Observer remembers the count it last returned, so clones can be handed
+/// out to tasks that each await changes independently. Like a WeakCounter,
+/// an Observer doesn't affect the count, and it counts towards `weak_count`.
+///
+/// [`WeakCounter::observer`]: crate::WeakCounter::observer
+#[derive(Debug, Clone)]
+pub struct Observer<T: CountInt = usize> {
+    weak: GenericWeakCounter<T>,
+    last: T,
+}
+
+impl<T: CountInt> Observer<T> {
+    pub(crate) fn new(weak: GenericWeakCounter<T>) -> Observer<T> {
+        Observer {
+            last: weak.count(),
+            weak,
+        }
+    }
+
+    /// The count as of the last call to `changed`, or as of the creation of
+    /// the first Observer this was cloned from.
+    #[inline]
+    pub fn last(&self) -> T {
+        self.last
+    }
+
+    /// Waits until the count differs from `last` and returns the new count.
+    ///
+    /// Rapid updates are coalesced like in `WeakCounter::count_stream`, and
+    /// a count that changes and changes back before this is polled goes
+    /// unnoticed. Cancellation safe: dropping the future before it resolves
+    /// leaves `last` untouched.
+    pub async fn changed(&mut self) -> T {
+        self.last = self.weak.counter.wait_for_change(self.last).await;
+        self.last
+    }
+}
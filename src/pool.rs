@@ -0,0 +1,89 @@
+use crate::{internal, WeakCounter};
+use std::sync::Mutex;
+
+/// Hands out Counters of size 1 on one count, recycling their handles
+/// through a free list instead of cloning a new handle for each.
+///
+/// A `PooledCounter` holds one unit of the count like a Counter and puts its
+/// handle back on the free list when dropped. Upgrades don't allocate either,
+/// so what the pool saves is the reference count update of each clone and
+/// drop, paid for with a lock on the free list; see the `pool` benchmark for
+/// whether that is a win for a given workload. The free list grows to the
+/// most PooledCounters held at once and never shrinks.
+///
+/// ```rust
+/// use raii_counter_futures::{CounterPool, WeakCounter};
+///
+/// let weak = WeakCounter::new();
+/// let pool = CounterPool::new(weak.clone());
+/// let counter = pool.acquire();
+/// assert_eq!(weak.count(), 1);
+/// drop(counter);
+/// assert_eq!(weak.count(), 0);
+/// assert_eq!(pool.idle(), 1);
+/// ```
+#[derive(Debug)]
+pub struct CounterPool {
+    weak: WeakCounter,
+    free: Mutex<Vec<internal::Counter>>,
+}
+
+/// Holds one unit of a `CounterPool`'s count until dropped, then returns
+/// its handle to the pool.
+#[derive(Debug)]
+#[must_use = "dropping the Counter immediately releases the count; bind it to a variable"]
+pub struct PooledCounter<'a> {
+    pool: &'a CounterPool,
+    counter: Option<internal::Counter>,
+}
+
+impl CounterPool {
+    pub fn new(weak: WeakCounter) -> CounterPool {
+        CounterPool {
+            weak,
+            free: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take one unit of the count, like `WeakCounter::spawn_upgrade`, reusing
+    /// a handle from the free list if there is one.
+    ///
+    /// Like `spawn_upgrade`, this panics if the counter is closed or full,
+    /// and otherwise revives a count that has drained to zero.
+    pub fn acquire(&self) -> PooledCounter<'_> {
+        let previous = self
+            .weak
+            .counter
+            .try_fetch_add(1)
+            .expect("acquired from a closed or full counter");
+        self.weak.counter.upgraded(previous, 1);
+
+        let counter = self.free.lock().unwrap().pop();
+        PooledCounter {
+            pool: self,
+            counter: Some(counter.unwrap_or_else(|| self.weak.counter.clone())),
+        }
+    }
+
+    /// The number of handles waiting on the free list.
+    pub fn idle(&self) -> usize {
+        self.free.lock().unwrap().len()
+    }
+}
+
+impl PooledCounter<'_> {
+    /// This method is inherently racey, see `Counter::count`.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.pool.weak.count()
+    }
+}
+
+impl Drop for PooledCounter<'_> {
+    fn drop(&mut self) {
+        if let Some(counter) = self.counter.take() {
+            counter.fetch_sub(1);
+            self.pool.free.lock().unwrap().push(counter);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use crate::{CountInt, GenericCounter, GenericWeakCounter};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+
+/// Serializes a snapshot of the count as `{ "count": N, "size": M }`.
+///
+/// The count is inherently racey, assume it will have changed by the time the
+/// serialized value is observed.
+impl<T: CountInt + Serialize> Serialize for GenericCounter<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Counter", 2)?;
+        state.serialize_field("count", &self.count())?;
+        state.serialize_field("size", &self.size())?;
+        state.end()
+    }
+}
+
+/// Serializes a snapshot of the count as `{ "count": N }`.
+///
+/// The count is inherently racey, assume it will have changed by the time the
+/// serialized value is observed.
+impl<T: CountInt + Serialize> Serialize for GenericWeakCounter<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("WeakCounter", 1)?;
+        state.serialize_field("count", &self.count())?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::WeakCounter;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn serializes_count_snapshot() {
+        let weak = WeakCounter::new();
+        let value: Value = serde_json::to_value(&weak).unwrap();
+        assert_eq!(value, json!({ "count": 0 }));
+
+        let counter = weak.spawn_upgrade_with_size(3);
+        let _clone = counter.clone();
+
+        let value: Value = serde_json::to_value(&counter).unwrap();
+        assert_eq!(value, json!({ "count": 6, "size": 3 }));
+
+        let text = serde_json::to_string(&weak).unwrap();
+        let value: Value = serde_json::from_str(&text).unwrap();
+        assert_eq!(value, json!({ "count": 6 }));
+    }
+}
@@ -0,0 +1,92 @@
+use crate::int::CountInt;
+use crate::internal;
+use event_listener::EventListener;
+use futures_core::Stream;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// Stream of count values, see [`WeakCounter::count_stream`].
+///
+/// [`WeakCounter::count_stream`]: crate::WeakCounter::count_stream
+pub(crate) struct CountStream<T: CountInt> {
+    counter: internal::Counter<T>,
+    last: Option<T>,
+    listener: Option<EventListener>,
+}
+
+impl<T: CountInt> CountStream<T> {
+    pub(crate) fn new(counter: internal::Counter<T>) -> CountStream<T> {
+        CountStream {
+            counter,
+            last: None,
+            listener: None,
+        }
+    }
+}
+
+impl<T: CountInt> Stream for CountStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            let count = self.counter.get();
+            if self.last != Some(count) {
+                self.last = Some(count);
+                self.listener = None;
+                return Poll::Ready(Some(count));
+            }
+
+            match &mut self.listener {
+                // Register and loop around to re-check the count, in case it
+                // changed before the listener existed.
+                None => self.listener = Some(self.counter.listen()),
+                Some(listener) => match Pin::new(listener).poll(cx) {
+                    Poll::Ready(()) => self.listener = None,
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
+
+/// Stream of drains, see [`WeakCounter::empty_events`].
+///
+/// [`WeakCounter::empty_events`]: crate::WeakCounter::empty_events
+pub(crate) struct EmptyEvents<T: CountInt> {
+    counter: internal::Counter<T>,
+    seen: usize,
+    listener: Option<EventListener>,
+}
+
+impl<T: CountInt> EmptyEvents<T> {
+    pub(crate) fn new(counter: internal::Counter<T>) -> EmptyEvents<T> {
+        EmptyEvents {
+            seen: counter.drains(),
+            counter,
+            listener: None,
+        }
+    }
+}
+
+impl<T: CountInt> Stream for EmptyEvents<T> {
+    type Item = ();
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<()>> {
+        loop {
+            if self.counter.drains() != self.seen {
+                self.seen = self.seen.wrapping_add(1);
+                self.listener = None;
+                return Poll::Ready(Some(()));
+            }
+
+            match &mut self.listener {
+                None => self.listener = Some(self.counter.listen_empty()),
+                Some(listener) => match Pin::new(listener).poll(cx) {
+                    Poll::Ready(()) => self.listener = None,
+                    Poll::Pending => return Poll::Pending,
+                },
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+//! The synchronization primitives the count is built from. The blocking
+//! wait's `Mutex` and `Condvar` come from `parking_lot` with the `parking_lot`
+//! feature, and everything is swapped for `loom`'s models when compiled with
+//! `--cfg loom` so the tests below can explore every interleaving of the
+//! atomic operations:
+//!
+//! ```text
+//! RUSTFLAGS="--cfg loom" cargo test --release --lib loom
+//! ```
+//!
+//! `event-listener` isn't modelled, so only the blocking waiter's
+//! `Mutex`/`Condvar` handshake is covered by loom.
+
+#[cfg(not(loom))]
+pub(crate) use alloc::sync::Arc;
+#[cfg(not(loom))]
+pub(crate) use core::hint::spin_loop;
+#[cfg(all(not(loom), feature = "tracing", debug_assertions))]
+pub(crate) use core::sync::atomic::AtomicBool;
+#[cfg(all(not(loom), target_has_atomic = "32"))]
+pub(crate) use core::sync::atomic::AtomicU32;
+#[cfg(all(not(loom), target_has_atomic = "64"))]
+pub(crate) use core::sync::atomic::AtomicU64;
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(all(not(loom), feature = "std", feature = "parking_lot"))]
+pub(crate) use parking_lot::{Condvar, Mutex};
+#[cfg(all(not(loom), feature = "std", not(feature = "parking_lot")))]
+pub(crate) use std::sync::{Condvar, Mutex};
+#[cfg(all(not(loom), feature = "std"))]
+pub(crate) use std::thread::yield_now;
+
+#[cfg(loom)]
+pub(crate) use loom::hint::spin_loop;
+#[cfg(all(loom, feature = "tracing", debug_assertions))]
+pub(crate) use loom::sync::atomic::AtomicBool;
+#[cfg(all(loom, target_has_atomic = "32"))]
+pub(crate) use loom::sync::atomic::AtomicU32;
+#[cfg(all(loom, target_has_atomic = "64"))]
+pub(crate) use loom::sync::atomic::AtomicU64;
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(loom)]
+pub(crate) use loom::sync::Arc;
+#[cfg(all(loom, feature = "std"))]
+pub(crate) use loom::sync::{Condvar, Mutex};
+#[cfg(all(loom, feature = "std"))]
+pub(crate) use loom::thread::yield_now;
+
+#[cfg(all(test, loom))]
+mod tests {
+    use crate::{Counter, WeakCounter};
+    use loom::sync::atomic::{AtomicUsize, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    #[test]
+    fn loom_concurrent_drops_drain_once() {
+        loom::model(|| {
+            let drains = Arc::new(AtomicUsize::new(0));
+            let on_empty = {
+                let drains = Arc::clone(&drains);
+                move || {
+                    drains.fetch_add(1, Ordering::Relaxed);
+                }
+            };
+            let counter1 = Counter::new_with_on_empty(1, on_empty);
+            let counter2 = counter1.clone();
+            let weak = counter1.downgrade_ref();
+
+            let handle1 = thread::spawn(move || drop(counter1));
+            let handle2 = thread::spawn(move || drop(counter2));
+            handle1.join().unwrap();
+            handle2.join().unwrap();
+
+            assert_eq!(weak.count(), 0);
+            assert_eq!(drains.load(Ordering::Relaxed), 1);
+        });
+    }
+
+    #[test]
+    fn loom_blocking_waiter_sees_drain() {
+        loom::model(|| {
+            let weak = WeakCounter::new();
+            let counter1 = weak.spawn_upgrade();
+            let counter2 = counter1.clone();
+
+            // One thread drops its handle while the other briefly takes the
+            // count back up before dropping its own, so the waiter can observe
+            // the count in any state. Loom reports a deadlock if a drain's
+            // wakeup is ever lost.
+            let handle1 = thread::spawn(move || drop(counter1));
+            let handle2 = {
+                let weak = weak.clone();
+                thread::spawn(move || {
+                    drop(weak.spawn_upgrade());
+                    drop(counter2);
+                })
+            };
+            weak.wait_for_empty_blocking();
+            assert_eq!(weak.count(), 0);
+
+            handle1.join().unwrap();
+            handle2.join().unwrap();
+        });
+    }
+}
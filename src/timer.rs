@@ -0,0 +1,41 @@
+//! The timer behind the `*_timeout` waits: tokio's with the `tokio-timer`
+//! feature, async-std's with `async-std-timer`, and a runtime-agnostic
+//! `Delay` driven by a shared timer thread otherwise. `tokio-timer` wins if
+//! both are enabled.
+
+use core::future::Future;
+use core::time::Duration;
+
+/// Resolves to the output of `future`, or `None` if `dur` elapses first.
+#[cfg(feature = "tokio-timer")]
+pub(crate) async fn timeout<F: Future>(dur: Duration, future: F) -> Option<F::Output> {
+    tokio::time::timeout(dur, future).await.ok()
+}
+
+/// Resolves to the output of `future`, or `None` if `dur` elapses first.
+#[cfg(all(feature = "async-std-timer", not(feature = "tokio-timer")))]
+pub(crate) async fn timeout<F: Future>(dur: Duration, future: F) -> Option<F::Output> {
+    async_std::future::timeout(dur, future).await.ok()
+}
+
+/// Resolves to the output of `future`, or `None` if `dur` elapses first.
+#[cfg(not(any(feature = "tokio-timer", feature = "async-std-timer")))]
+pub(crate) async fn timeout<F: Future>(dur: Duration, future: F) -> Option<F::Output> {
+    use crate::delay::Delay;
+    use core::future;
+    use core::pin::{pin, Pin};
+    use core::task::Poll;
+
+    let mut future = pin!(future);
+    let mut delay = Delay::new(dur);
+    future::poll_fn(|cx| {
+        if let Poll::Ready(output) = future.as_mut().poll(cx) {
+            Poll::Ready(Some(output))
+        } else if Pin::new(&mut delay).poll(cx).is_ready() {
+            Poll::Ready(None)
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,58 @@
+use crate::{Counter, WeakCounter};
+
+/// Waits for a group of tasks to finish, in the style of Go's
+/// `sync.WaitGroup`. Each task holds a `WaitGroupGuard` from `add`, and
+/// `wait` resolves once every guard has been dropped.
+///
+/// Clones share the same group.
+///
+/// ```rust
+/// use raii_counter_futures::WaitGroup;
+///
+/// # futures::executor::block_on(async {
+/// let group = WaitGroup::new();
+/// let guard = group.add();
+/// std::thread::spawn(move || drop(guard));
+/// group.wait().await;
+/// # });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct WaitGroup {
+    weak: WeakCounter,
+}
+
+/// Keeps its `WaitGroup` from completing until dropped. Cloning a guard adds
+/// another member to the group.
+#[derive(Debug, Clone)]
+pub struct WaitGroupGuard {
+    _counter: Counter,
+}
+
+impl WaitGroup {
+    pub fn new() -> WaitGroup {
+        WaitGroup {
+            weak: WeakCounter::new(),
+        }
+    }
+
+    /// Add a member to the group, which leaves it when the returned guard is
+    /// dropped.
+    pub fn add(&self) -> WaitGroupGuard {
+        WaitGroupGuard {
+            _counter: self.weak.spawn_upgrade(),
+        }
+    }
+
+    /// The number of guards currently held. This method is inherently racey,
+    /// see `WeakCounter::count`.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.weak.count()
+    }
+
+    /// Wait until every guard has been dropped. Resolves immediately if none
+    /// are held.
+    pub async fn wait(&self) {
+        self.weak.wait_for_empty().await
+    }
+}
@@ -0,0 +1,48 @@
+use crate::{internal, CountInt, GenericWeakCounter};
+
+/// A WeakCounter borrowed from a Counter, see [`GenericCounter::as_weak`].
+///
+/// Unlike a WeakCounter it doesn't hold on to the count itself, so creating
+/// one costs nothing, but it can't outlive the Counter it was borrowed from.
+/// It doesn't count towards `weak_count`.
+///
+/// [`GenericCounter::as_weak`]: crate::GenericCounter::as_weak
+#[derive(Debug, Clone, Copy)]
+pub struct WeakCounterRef<'a, T: CountInt = usize> {
+    counter: &'a internal::Counter<T>,
+}
+
+impl<'a, T: CountInt> WeakCounterRef<'a, T> {
+    pub(crate) fn new(counter: &'a internal::Counter<T>) -> WeakCounterRef<'a, T> {
+        WeakCounterRef { counter }
+    }
+
+    /// This method is inherently racey, see `WeakCounter::count`.
+    #[inline]
+    pub fn count(&self) -> T {
+        self.counter.get()
+    }
+
+    /// Whether the count is currently zero. Just as racey as `count`.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.count() == T::ZERO
+    }
+
+    /// A WeakCounter of the same count, for when the view needs to outlive
+    /// the Counter after all.
+    pub fn to_weak(&self) -> GenericWeakCounter<T> {
+        GenericWeakCounter::from_counter(self.counter.clone())
+    }
+
+    /// Returns a future that waits until the counter contains a 0 value, see
+    /// `WeakCounter::wait_for_empty`.
+    ///
+    /// The Counter this was borrowed from is alive for as long as the view,
+    /// so unless its size is zero this only resolves once the count has been
+    /// desynced, e.g. through `WeakCounter::release`.
+    #[cfg(feature = "std")]
+    pub async fn wait_for_empty(&self) {
+        self.counter.wait_for_empty().await;
+    }
+}
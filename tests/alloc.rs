@@ -0,0 +1,52 @@
+#[cfg(feature = "std")]
+use raii_counter_futures::CounterPool;
+use raii_counter_futures::WeakCounter;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the allocations made by this test binary.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+#[test]
+fn upgrades_and_drops_do_not_allocate() {
+    let weak = WeakCounter::new();
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..1000 {
+        let counter = weak.spawn_upgrade();
+        drop(counter.clone());
+        drop(counter);
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn warm_pool_acquires_do_not_allocate() {
+    let pool = CounterPool::new(WeakCounter::new());
+    drop((pool.acquire(), pool.acquire()));
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    for _ in 0..1000 {
+        let first = pool.acquire();
+        let second = pool.acquire();
+        drop(first);
+        drop(second);
+    }
+    assert_eq!(ALLOCATIONS.load(Ordering::SeqCst), before);
+}
@@ -0,0 +1,11 @@
+#![deny(unused_must_use)]
+
+use raii_counter_futures::WeakCounter;
+
+fn main() {
+    let weak = WeakCounter::new();
+    weak.spawn_upgrade();
+    weak.try_spawn_upgrade();
+    weak.spawn_upgrade_many(2);
+    weak.try_upgrade();
+}
@@ -0,0 +1,8 @@
+use raii_counter_futures::Counter;
+use std::rc::Rc;
+
+fn main() {
+    // Any thread may drain the count, so on_empty must be Send + Sync.
+    let drains = Rc::new(());
+    let _counter = Counter::new_with_on_empty(1, move || drop(Rc::clone(&drains)));
+}